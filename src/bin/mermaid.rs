@@ -68,7 +68,7 @@ fn main() -> Result<(), String> {
         &theme.code_bg_color,
     );
 
-    let mut measure = CosmicTextMeasure::new()?;
+    let mut measure = CosmicTextMeasure::with_fallbacks(&theme.fonts)?;
     let (inner_svg, width, height) = render_diagram(&source, &style, &mut measure)?;
 
     let pad = args.padding;
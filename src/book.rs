@@ -0,0 +1,219 @@
+//! Books: several markdown files rendered into one paginated document with
+//! a cover, a table of contents and continuous page numbers.
+//!
+//! The chapters come from a list of files, from an mdBook-style
+//! `SUMMARY.md` (every link in it, in order), or from a TOML manifest:
+//!
+//! ```toml
+//! [book]
+//! title = "User Guide"
+//! subtitle = "Version 2"
+//! authors = ["Ada", "Grace"]
+//! date = 2024-05-01
+//! chapters = ["intro.md", "setup/install.md"]
+//! ```
+//!
+//! A manifest without `chapters` reads `SUMMARY.md` from its `src`
+//! directory (`src` by default, as in mdBook).
+
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// What the cover shows. Empty fields are filled from the first chapter
+/// (its front matter `title` and `date`, or its first H1).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookMeta {
+    pub title: Option<String>,
+    pub subtitle: Option<String>,
+    pub authors: Vec<String>,
+    pub date: Option<String>,
+}
+
+/// One chapter's source. Its images and links resolve relative to `path`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub path: PathBuf,
+    pub markdown: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Book {
+    pub meta: BookMeta,
+    pub chapters: Vec<Chapter>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    book: ManifestBook,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ManifestBook {
+    title: Option<String>,
+    subtitle: Option<String>,
+    #[serde(alias = "author", deserialize_with = "deserialize_authors")]
+    authors: Vec<String>,
+    #[serde(deserialize_with = "crate::frontmatter::deserialize_date")]
+    date: Option<String>,
+    chapters: Vec<PathBuf>,
+    src: Option<PathBuf>,
+}
+
+/// Accept `author = "Ada"` as well as `authors = ["Ada", "Grace"]`.
+fn deserialize_authors<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Authors {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match Authors::deserialize(deserializer)? {
+        Authors::One(author) => vec![author],
+        Authors::Many(authors) => authors,
+    })
+}
+
+impl Book {
+    /// Load a book from `inputs`: a single `.toml` manifest or `SUMMARY.md`,
+    /// or the chapter files themselves in order.
+    pub fn load(inputs: &[PathBuf]) -> Result<Self, String> {
+        let (meta, paths) = match inputs {
+            [input] if has_extension(input, "toml") => read_manifest(input)?,
+            [input] if is_summary(input) => {
+                let (title, paths) = read_summary(input)?;
+                let meta = BookMeta {
+                    title,
+                    ..BookMeta::default()
+                };
+                (meta, paths)
+            }
+            _ => (BookMeta::default(), inputs.to_vec()),
+        };
+        if paths.is_empty() {
+            return Err("The book has no chapters".to_string());
+        }
+        let chapters = paths
+            .into_iter()
+            .map(|path| {
+                let markdown = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                Ok(Chapter { path, markdown })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { meta, chapters })
+    }
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+fn is_summary(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.eq_ignore_ascii_case("SUMMARY.md"))
+}
+
+fn read_manifest(path: &Path) -> Result<(BookMeta, Vec<PathBuf>), String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let manifest: Manifest = toml::from_str(&source)
+        .map_err(|e| format!("Failed to parse book manifest {}: {}", path.display(), e))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let book = manifest.book;
+    let mut meta = BookMeta {
+        title: book.title,
+        subtitle: book.subtitle,
+        authors: book.authors,
+        date: book.date,
+    };
+    if !book.chapters.is_empty() {
+        let chapters = book.chapters.iter().map(|chapter| dir.join(chapter));
+        return Ok((meta, chapters.collect()));
+    }
+    let summary = dir
+        .join(book.src.as_deref().unwrap_or(Path::new("src")))
+        .join("SUMMARY.md");
+    let (title, chapters) = read_summary(&summary)?;
+    meta.title = meta.title.or(title);
+    Ok((meta, chapters))
+}
+
+fn read_summary(path: &Path) -> Result<(Option<String>, Vec<PathBuf>), String> {
+    let markdown = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let (title, links) = parse_summary(&markdown);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    Ok((
+        title,
+        links.into_iter().map(|link| dir.join(link)).collect(),
+    ))
+}
+
+/// The title (first H1) and chapter links of a `SUMMARY.md`, in order.
+/// Draft chapters without a link, external URLs and repeats are skipped.
+pub fn parse_summary(markdown: &str) -> (Option<String>, Vec<String>) {
+    let mut title = None;
+    let mut heading = None::<String>;
+    let mut links: Vec<String> = Vec::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H1,
+                ..
+            }) if title.is_none() => heading = Some(String::new()),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = &mut heading {
+                    heading.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                title = title.or(heading.take().map(|text| text.trim().to_string()));
+            }
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                let link = dest_url.split('#').next().unwrap_or_default();
+                if !link.is_empty() && !link.contains("://") && !links.iter().any(|l| l == link) {
+                    links.push(link.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    (title, links)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_chapters_from_a_summary() {
+        let summary = "# The `markie` Book\n\n[Preface](preface.md)\n\n- [Intro](intro.md)\n  - [Setup](setup/install.md#linux)\n- [Later]()\n- [Site](https://example.com)\n- [Intro again](intro.md)\n";
+        let (title, links) = parse_summary(summary);
+        assert_eq!(title.as_deref(), Some("The markie Book"));
+        assert_eq!(links, vec!["preface.md", "intro.md", "setup/install.md"]);
+    }
+
+    #[test]
+    fn parses_manifest_metadata() {
+        let manifest: Manifest = toml::from_str(
+            "[book]\ntitle = \"Guide\"\nauthor = \"Ada\"\ndate = 2024-05-01\nchapters = [\"a.md\"]\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.book.title.as_deref(), Some("Guide"));
+        assert_eq!(manifest.book.authors, vec!["Ada"]);
+        assert_eq!(manifest.book.date.as_deref(), Some("2024-05-01"));
+        assert_eq!(manifest.book.chapters, vec![PathBuf::from("a.md")]);
+
+        let manifest: Manifest =
+            toml::from_str("[book]\nauthors = [\"Ada\", \"Grace\"]\n").unwrap();
+        assert_eq!(manifest.book.authors.len(), 2);
+        assert!(manifest.book.chapters.is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+//! Multi-column layout: placing a document laid out as one narrow column
+//! into several balanced columns.
+//!
+//! The renderer lays flowing content out at the width of one column, and
+//! blocks that span the page at full width, one after the other down a
+//! single strip. Each run of flowing content is then cut at the same break
+//! points pagination uses into columns of (nearly) equal height, placed
+//! side by side; spanning blocks sit between the runs.
+
+use crate::pages::page_ranges;
+use std::ops::Range;
+
+/// A vertical stretch of the laid-out strip.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlowSegment {
+    /// Content flowing through the columns.
+    Columns(Range<f32>),
+    /// A block as wide as the page.
+    Span(Range<f32>),
+}
+
+/// Part of the strip drawn with its top at (`x`, `y`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub source: Range<f32>,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Where each part of the strip ends up.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnLayout {
+    pub placements: Vec<Placement>,
+    /// Bottom of the placed content.
+    pub bottom: f32,
+    /// Where a page may end in the placed content: below each run of
+    /// columns, and at the breaks inside spanning blocks.
+    pub breaks: Vec<f32>,
+}
+
+impl ColumnLayout {
+    /// Place `segments` starting at `top`, in `count` columns `pitch` apart
+    /// (column width plus gutter). `breaks` are the strip's break points.
+    pub fn new(
+        segments: &[FlowSegment],
+        breaks: &[f32],
+        count: usize,
+        pitch: f32,
+        top: f32,
+    ) -> Self {
+        let mut layout = Self {
+            bottom: top,
+            ..Self::default()
+        };
+        for segment in segments {
+            match segment {
+                FlowSegment::Span(source) if source.end > source.start => {
+                    let y = layout.bottom;
+                    if layout.breaks.last() != Some(&y) {
+                        layout.breaks.push(y);
+                    }
+                    layout.breaks.extend(
+                        breaks
+                            .iter()
+                            .filter(|&&b| b > source.start && b < source.end)
+                            .map(|b| y + b - source.start),
+                    );
+                    layout.placements.push(Placement {
+                        source: source.clone(),
+                        x: 0.0,
+                        y,
+                    });
+                    layout.bottom += source.end - source.start;
+                }
+                FlowSegment::Columns(source) if source.end > source.start => {
+                    let y = layout.bottom;
+                    let pieces = balance(breaks, source.clone(), count);
+                    let height = pieces
+                        .iter()
+                        .map(|piece| piece.end - piece.start)
+                        .fold(0.0, f32::max);
+                    for (index, piece) in pieces.into_iter().enumerate() {
+                        layout.placements.push(Placement {
+                            source: piece,
+                            x: index as f32 * pitch,
+                            y,
+                        });
+                    }
+                    layout.bottom += height;
+                    layout.breaks.push(layout.bottom);
+                }
+                _ => {}
+            }
+        }
+        layout
+    }
+
+    /// Where strip position `y` was placed (e.g. a heading's top).
+    pub fn map_y(&self, y: f32) -> f32 {
+        self.placements
+            .iter()
+            .find(|placement| placement.source.contains(&y))
+            .or_else(|| {
+                self.placements
+                    .iter()
+                    .rfind(|placement| placement.source.start <= y)
+            })
+            .map_or(y, |placement| placement.y + y - placement.source.start)
+    }
+}
+
+/// Cut `range` into at most `count` consecutive pieces at `breaks`, keeping
+/// the tallest as short as possible. Content is never cut between breaks,
+/// so a run without any stays in one column.
+pub fn balance(breaks: &[f32], range: Range<f32>, count: usize) -> Vec<Range<f32>> {
+    let total = range.end - range.start;
+    if count <= 1 || total <= 1.0 {
+        return vec![range];
+    }
+    let fits = |pieces: &[Range<f32>]| {
+        pieces.len() <= count
+            && pieces[..pieces.len() - 1]
+                .iter()
+                .all(|piece| breaks.contains(&piece.end))
+    };
+    let (mut low, mut high) = (total / count as f32, total);
+    let mut best = vec![range.clone()];
+    for _ in 0..24 {
+        if high - low < 0.5 {
+            break;
+        }
+        let height = (low + high) / 2.0;
+        let pieces = page_ranges(breaks, range.start, range.end, height);
+        if fits(&pieces) {
+            high = height;
+            best = pieces;
+        } else {
+            low = height;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balances_runs_around_spanning_blocks() {
+        let breaks = [20.0, 40.0, 60.0, 80.0, 150.0, 170.0];
+        assert_eq!(
+            balance(&breaks, 0.0..100.0, 2),
+            vec![0.0..60.0, 60.0..100.0]
+        );
+        assert_eq!(balance(&breaks, 0.0..100.0, 1), vec![0.0..100.0]);
+        assert_eq!(balance(&breaks, 90.0..140.0, 2), vec![90.0..140.0]);
+
+        let layout = ColumnLayout::new(
+            &[
+                FlowSegment::Columns(0.0..100.0),
+                FlowSegment::Span(100.0..200.0),
+                FlowSegment::Columns(200.0..200.0),
+            ],
+            &breaks,
+            2,
+            300.0,
+            0.0,
+        );
+        assert_eq!(
+            layout.placements,
+            vec![
+                Placement {
+                    source: 0.0..60.0,
+                    x: 0.0,
+                    y: 0.0
+                },
+                Placement {
+                    source: 60.0..100.0,
+                    x: 300.0,
+                    y: 0.0
+                },
+                Placement {
+                    source: 100.0..200.0,
+                    x: 0.0,
+                    y: 60.0
+                },
+            ]
+        );
+        assert_eq!(layout.bottom, 160.0);
+        assert_eq!(layout.breaks, vec![60.0, 110.0, 130.0]);
+        assert_eq!(layout.map_y(70.0), 10.0);
+        assert_eq!(layout.map_y(150.0), 110.0);
+    }
+}
@@ -0,0 +1,421 @@
+//! Font embedding for SVG output (`--embed-fonts`).
+//!
+//! Browsers substitute whatever they have installed for the generic
+//! `sans-serif`/`serif`/`monospace` families the renderer writes, so text laid
+//! out against cosmic-text metrics can overflow its boxes. Embedding writes the
+//! faces cosmic-text actually resolved as `@font-face` rules, subsetted to the
+//! glyphs the document uses, and points every `<text>` element at them.
+
+use base64::Engine;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::fonts::{self, FontKey, ResolvedFace};
+
+/// A `<text>` element found in the SVG: byte range of its `font-family` value,
+/// its font selection and its (unescaped) content.
+struct TextElement {
+    family_value: std::ops::Range<usize>,
+    key: FontKey,
+    content: String,
+}
+
+/// Embed subsetted copies of every font face used by `svg` and rewrite the
+/// `font-family` attributes to reference them (keeping the generic family as
+/// the last fallback).
+pub fn embed_fonts(svg: &str) -> Result<String, String> {
+    let elements = scan_text_elements(svg);
+    if elements.is_empty() {
+        return Ok(svg.to_string());
+    }
+
+    let resolved = fonts::resolve_faces(elements.iter().map(|e| (&e.key, e.content.as_str())));
+
+    let mut css = String::new();
+    let mut embedded: BTreeMap<usize, String> = BTreeMap::new();
+    for (slot, face) in resolved.faces.iter().enumerate() {
+        let (data, format) = match subset_face(face) {
+            Ok(subset) => subset,
+            Err(e) => {
+                eprintln!("Warning: could not subset font for embedding: {}", e);
+                continue;
+            }
+        };
+        let family = format!("markie-embedded-{}", slot);
+        write!(
+            css,
+            "@font-face{{font-family:'{}';src:url(data:font/{};base64,{}) format('{}');font-weight:{};font-style:{};}}",
+            family,
+            if format == "opentype" { "otf" } else { "ttf" },
+            base64::engine::general_purpose::STANDARD.encode(&data),
+            format,
+            face.weight,
+            if face.italic { "italic" } else { "normal" },
+        )
+        .unwrap();
+        embedded.insert(slot, family);
+    }
+
+    if embedded.is_empty() {
+        return Ok(svg.to_string());
+    }
+
+    // Rewrite font-family values back to front so earlier ranges stay valid.
+    let mut out = svg.to_string();
+    for element in elements.iter().rev() {
+        let Some(chain) = resolved.chains.get(&element.key) else {
+            continue;
+        };
+        let mut families: Vec<String> = chain
+            .iter()
+            .filter_map(|slot| embedded.get(slot))
+            .map(|family| format!("'{}'", family))
+            .collect();
+        if families.is_empty() {
+            continue;
+        }
+        families.push(element.key.family.clone());
+        out.replace_range(element.family_value.clone(), &families.join(", "));
+    }
+
+    let Some(open_end) = out
+        .find("<svg")
+        .and_then(|start| out[start..].find('>').map(|i| start + i + 1))
+    else {
+        return Err("SVG has no root element".to_string());
+    };
+    out.insert_str(open_end, &format!("<defs><style>{}</style></defs>", css));
+    Ok(out)
+}
+
+/// Find every `<text>` element written by the renderer. The SVG is our own
+/// output, so attributes are always double-quoted and content never nests
+/// further elements.
+fn scan_text_elements(svg: &str) -> Vec<TextElement> {
+    let mut elements = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = svg[pos..].find("<text ") {
+        let tag_start = pos + found;
+        let Some(tag_len) = svg[tag_start..].find('>') else {
+            break;
+        };
+        let tag_end = tag_start + tag_len;
+        let content_start = tag_end + 1;
+        let Some(content_len) = svg[content_start..].find("</text>") else {
+            break;
+        };
+        pos = content_start + content_len;
+
+        let tag = &svg[tag_start..tag_end];
+        let Some(family_value) = attr_range(tag, "font-family") else {
+            continue;
+        };
+        let family = &tag[family_value.clone()];
+        // Only generic families are resolved through cosmic-text; anything
+        // else was chosen explicitly and is left alone.
+        if !matches!(family, "sans-serif" | "serif" | "monospace") {
+            continue;
+        }
+        let weight = attr_range(tag, "font-weight")
+            .map(|range| match &tag[range] {
+                "bold" | "bolder" => 700,
+                "normal" => 400,
+                value => value.parse().unwrap_or(400),
+            })
+            .unwrap_or(400);
+        let italic = attr_range(tag, "font-style").is_some_and(|range| &tag[range] != "normal");
+
+        let raw = &svg[content_start..pos];
+        let content = quick_xml::escape::unescape(raw)
+            .map(|c| c.into_owned())
+            .unwrap_or_else(|_| raw.to_string());
+
+        elements.push(TextElement {
+            family_value: tag_start + family_value.start..tag_start + family_value.end,
+            key: FontKey {
+                family: family.to_string(),
+                weight,
+                italic,
+            },
+            content,
+        });
+    }
+    elements
+}
+
+/// Byte range of a double-quoted attribute value within a start tag.
+fn attr_range(tag: &str, name: &str) -> Option<std::ops::Range<usize>> {
+    let needle = format!(" {}=\"", name);
+    let start = tag.find(&needle)? + needle.len();
+    let len = tag[start..].find('"')?;
+    Some(start..start + len)
+}
+
+/// Subset a face to the glyphs its characters map to. Returns the font data
+/// and its CSS `format()` hint.
+fn subset_face(face: &ResolvedFace) -> Result<(Vec<u8>, &'static str), String> {
+    let parsed = ttf_parser::Face::parse(&face.data, face.index)
+        .map_err(|e| format!("failed to parse font: {}", e))?;
+
+    let mut remapper = subsetter::GlyphRemapper::new();
+    let mut mapping: Vec<(char, u16)> = Vec::new();
+    for &c in &face.chars {
+        if let Some(glyph) = parsed.glyph_index(c) {
+            mapping.push((c, remapper.remap(glyph.0)));
+        }
+    }
+
+    let subset = subsetter::subset(&face.data, face.index, &remapper)
+        .map_err(|e| format!("failed to subset font: {}", e))?;
+
+    // The subsetter targets PDF embedding and drops `cmap` and `OS/2`, both of
+    // which browsers require. Rebuild the cmap for the remapped glyph IDs and
+    // carry `OS/2` over unchanged (it references no glyph IDs).
+    let mut extra: Vec<([u8; 4], Vec<u8>)> = vec![(*b"cmap", build_cmap(&mapping))];
+    if let Some(os2) = parsed
+        .raw_face()
+        .table(ttf_parser::Tag::from_bytes(b"OS/2"))
+    {
+        extra.push((*b"OS/2", os2.to_vec()));
+    }
+
+    let format = if subset.starts_with(b"OTTO") {
+        "opentype"
+    } else {
+        "truetype"
+    };
+    Ok((add_tables(&subset, extra)?, format))
+}
+
+/// Build a `cmap` table with a BMP format 4 subtable and a full-range format
+/// 12 subtable for the given `(char, glyph)` pairs.
+fn build_cmap(mapping: &[(char, u16)]) -> Vec<u8> {
+    let bmp: Vec<(u16, u16)> = mapping
+        .iter()
+        .filter(|(c, _)| (*c as u32) < 0xFFFF)
+        .map(|&(c, g)| (c as u32 as u16, g))
+        .collect();
+
+    // Format 4: one segment per character plus the mandatory 0xFFFF segment.
+    let seg_count = bmp.len() as u16 + 1;
+    let entry_selector = 15 - (seg_count.leading_zeros() as u16);
+    let search_range = 2 * (1u16 << entry_selector);
+    let mut end_codes = Vec::new();
+    let mut start_codes = Vec::new();
+    let mut deltas = Vec::new();
+    for &(code, glyph) in &bmp {
+        end_codes.push(code);
+        start_codes.push(code);
+        deltas.push(glyph.wrapping_sub(code));
+    }
+    end_codes.push(0xFFFF);
+    start_codes.push(0xFFFF);
+    deltas.push(1);
+
+    let mut format4 = Vec::new();
+    let length = 16 + seg_count as usize * 8;
+    for value in [
+        4,
+        length as u16,
+        0,
+        seg_count * 2,
+        search_range,
+        entry_selector,
+        seg_count * 2 - search_range,
+    ] {
+        format4.extend_from_slice(&value.to_be_bytes());
+    }
+    end_codes
+        .iter()
+        .for_each(|v| format4.extend_from_slice(&v.to_be_bytes()));
+    format4.extend_from_slice(&0u16.to_be_bytes());
+    start_codes
+        .iter()
+        .for_each(|v| format4.extend_from_slice(&v.to_be_bytes()));
+    deltas
+        .iter()
+        .for_each(|v| format4.extend_from_slice(&v.to_be_bytes()));
+    (0..seg_count).for_each(|_| format4.extend_from_slice(&0u16.to_be_bytes()));
+
+    // Format 12: one group per character.
+    let mut format12 = Vec::new();
+    format12.extend_from_slice(&12u16.to_be_bytes());
+    format12.extend_from_slice(&0u16.to_be_bytes());
+    format12.extend_from_slice(&(16 + mapping.len() as u32 * 12).to_be_bytes());
+    format12.extend_from_slice(&0u32.to_be_bytes());
+    format12.extend_from_slice(&(mapping.len() as u32).to_be_bytes());
+    for &(c, glyph) in mapping {
+        format12.extend_from_slice(&(c as u32).to_be_bytes());
+        format12.extend_from_slice(&(c as u32).to_be_bytes());
+        format12.extend_from_slice(&(glyph as u32).to_be_bytes());
+    }
+
+    let header_len = 4 + 2 * 8;
+    let mut cmap = Vec::new();
+    cmap.extend_from_slice(&0u16.to_be_bytes());
+    cmap.extend_from_slice(&2u16.to_be_bytes());
+    for (encoding, offset) in [
+        (1u16, header_len as u32),
+        (10u16, (header_len + format4.len()) as u32),
+    ] {
+        cmap.extend_from_slice(&3u16.to_be_bytes());
+        cmap.extend_from_slice(&encoding.to_be_bytes());
+        cmap.extend_from_slice(&offset.to_be_bytes());
+    }
+    cmap.extend(format4);
+    cmap.extend(format12);
+    cmap
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16, String> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| "truncated font data".to_string())
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32, String> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "truncated font data".to_string())
+}
+
+fn table_checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// Reassemble an sfnt with additional tables (replacing any with the same tag).
+fn add_tables(font: &[u8], extra: Vec<([u8; 4], Vec<u8>)>) -> Result<Vec<u8>, String> {
+    let flavor = read_u32(font, 0)?;
+    let count = read_u16(font, 4)? as usize;
+
+    let mut tables: BTreeMap<[u8; 4], Vec<u8>> = BTreeMap::new();
+    for i in 0..count {
+        let record = 12 + i * 16;
+        let tag: [u8; 4] = font
+            .get(record..record + 4)
+            .and_then(|t| t.try_into().ok())
+            .ok_or("truncated font data")?;
+        let offset = read_u32(font, record + 8)? as usize;
+        let length = read_u32(font, record + 12)? as usize;
+        let data = font
+            .get(offset..offset + length)
+            .ok_or("font table out of bounds")?;
+        tables.insert(tag, data.to_vec());
+    }
+    for (tag, data) in extra {
+        tables.insert(tag, data);
+    }
+
+    let count = tables.len() as u16;
+    let entry_selector = 15 - count.leading_zeros() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&flavor.to_be_bytes());
+    for value in [
+        count,
+        search_range,
+        entry_selector,
+        count * 16 - search_range,
+    ] {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    let mut offset = 12 + tables.len() * 16;
+    let mut head_offset = None;
+    for (tag, data) in tables.iter_mut() {
+        if tag == b"head" && data.len() >= 12 {
+            data[8..12].fill(0);
+            head_offset = Some(offset);
+        }
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&table_checksum(data).to_be_bytes());
+        out.extend_from_slice(&(offset as u32).to_be_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += data.len().div_ceil(4) * 4;
+    }
+    for data in tables.values() {
+        out.extend_from_slice(data);
+        out.resize(out.len().div_ceil(4) * 4, 0);
+    }
+
+    if let Some(head) = head_offset {
+        let adjustment = 0xB1B0_AFBAu32.wrapping_sub(table_checksum(&out));
+        out[head + 8..head + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Characters covered by a subsetted font's cmap.
+    fn covered_chars(font: &[u8]) -> std::collections::BTreeSet<char> {
+        let face = ttf_parser::Face::parse(font, 0).expect("valid font");
+        let mut chars = std::collections::BTreeSet::new();
+        if let Some(cmap) = face.tables().cmap {
+            for subtable in cmap.subtables {
+                subtable.codepoints(|cp| {
+                    if let Some(c) = char::from_u32(cp) {
+                        chars.insert(c);
+                    }
+                });
+            }
+        }
+        chars
+    }
+
+    #[test]
+    fn subset_keeps_only_requested_glyphs_with_working_cmap() {
+        let key = FontKey {
+            family: "sans-serif".to_string(),
+            weight: 400,
+            italic: false,
+        };
+        let resolved = fonts::resolve_faces([(&key, "Hello")]);
+        let Some(face) = resolved.faces.first() else {
+            // No system fonts available to shape with.
+            return;
+        };
+        assert_eq!(face.chars, "Helo".chars().collect());
+
+        let (subset, _format) = subset_face(face).expect("subset");
+        assert!(subset.len() < face.data.len());
+
+        let parsed = ttf_parser::Face::parse(&subset, 0).expect("subset parses");
+        for c in "Helo".chars() {
+            assert!(parsed.glyph_index(c).is_some(), "missing glyph for {c}");
+        }
+        assert!(parsed.glyph_index('Z').is_none());
+        assert_eq!(covered_chars(&subset), "Helo".chars().collect());
+        assert!(
+            parsed
+                .raw_face()
+                .table(ttf_parser::Tag::from_bytes(b"OS/2"))
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn scan_finds_generic_families_and_unescapes_content() {
+        let svg = r#"<svg><text x="1" y="2" font-family="monospace" font-size="13" fill="red" font-weight="700">a&amp;b&#160;c</text><text x="1" y="2" font-family="Custom" fill="red">skip</text></svg>"#;
+        let elements = scan_text_elements(svg);
+
+        assert_eq!(elements.len(), 1);
+        assert_eq!(elements[0].content, "a&b\u{a0}c");
+        assert_eq!(elements[0].key.family, "monospace");
+        assert_eq!(elements[0].key.weight, 700);
+        assert_eq!(&svg[elements[0].family_value.clone()], "monospace");
+    }
+
+    #[test]
+    fn svg_without_text_is_unchanged() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg"><rect width="1" height="1" /></svg>"#;
+        assert_eq!(embed_fonts(svg).unwrap(), svg);
+    }
+}
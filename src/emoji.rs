@@ -0,0 +1,578 @@
+//! Emoji shortcodes (`:rocket:`) and emoji drawn as images.
+//!
+//! SVG viewers draw emoji with whatever emoji font they have, while PNG and
+//! PDF export use the fonts markie measured with. To make all formats match,
+//! runs of emoji can be drawn through the export pipeline into a small
+//! vector image (color glyph layers, bitmaps or outlines) that is embedded
+//! in the SVG instead of text.
+
+use resvg::usvg;
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::ops::Range;
+
+/// GitHub shortcode names and their emoji, sorted by name.
+static SHORTCODES: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("100", "💯"),
+    ("1234", "🔢"),
+    ("8ball", "🎱"),
+    ("a", "🅰️"),
+    ("ab", "🆎"),
+    ("abc", "🔤"),
+    ("airplane", "✈️"),
+    ("alarm_clock", "⏰"),
+    ("alien", "👽"),
+    ("ambulance", "🚑"),
+    ("anchor", "⚓"),
+    ("angel", "👼"),
+    ("anger", "💢"),
+    ("angry", "😠"),
+    ("apple", "🍎"),
+    ("arrow_backward", "◀️"),
+    ("arrow_down", "⬇️"),
+    ("arrow_forward", "▶️"),
+    ("arrow_left", "⬅️"),
+    ("arrow_right", "➡️"),
+    ("arrow_up", "⬆️"),
+    ("arrows_counterclockwise", "🔄"),
+    ("art", "🎨"),
+    ("astonished", "😲"),
+    ("atom_symbol", "⚛️"),
+    ("b", "🅱️"),
+    ("baby", "👶"),
+    ("balloon", "🎈"),
+    ("ballot_box_with_check", "☑️"),
+    ("bangbang", "‼️"),
+    ("bar_chart", "📊"),
+    ("battery", "🔋"),
+    ("beer", "🍺"),
+    ("beers", "🍻"),
+    ("beetle", "🐞"),
+    ("bell", "🔔"),
+    ("bike", "🚲"),
+    ("bird", "🐦"),
+    ("birthday", "🎂"),
+    ("black_circle", "⚫"),
+    ("blue_heart", "💙"),
+    ("blush", "😊"),
+    ("bomb", "💣"),
+    ("book", "📖"),
+    ("bookmark", "🔖"),
+    ("books", "📚"),
+    ("boom", "💥"),
+    ("bow", "🙇"),
+    ("brain", "🧠"),
+    ("bricks", "🧱"),
+    ("broken_heart", "💔"),
+    ("bug", "🐛"),
+    ("bulb", "💡"),
+    ("bust_in_silhouette", "👤"),
+    ("busts_in_silhouette", "👥"),
+    ("cake", "🍰"),
+    ("calendar", "📆"),
+    ("camera", "📷"),
+    ("car", "🚗"),
+    ("card_index", "📇"),
+    ("cat", "🐱"),
+    ("chart_with_downwards_trend", "📉"),
+    ("chart_with_upwards_trend", "📈"),
+    ("check", "✔️"),
+    ("checkered_flag", "🏁"),
+    ("cherries", "🍒"),
+    ("christmas_tree", "🎄"),
+    ("clap", "👏"),
+    ("clipboard", "📋"),
+    ("clock1", "🕐"),
+    ("closed_lock_with_key", "🔐"),
+    ("cloud", "☁️"),
+    ("coffee", "☕"),
+    ("cold_sweat", "😰"),
+    ("collision", "💥"),
+    ("computer", "💻"),
+    ("confetti_ball", "🎊"),
+    ("confounded", "😖"),
+    ("confused", "😕"),
+    ("construction", "🚧"),
+    ("construction_worker", "👷"),
+    ("cookie", "🍪"),
+    ("cool", "🆒"),
+    ("copyright", "©️"),
+    ("crab", "🦀"),
+    ("crossed_fingers", "🤞"),
+    ("crown", "👑"),
+    ("cry", "😢"),
+    ("crystal_ball", "🔮"),
+    ("cupid", "💘"),
+    ("dart", "🎯"),
+    ("dash", "💨"),
+    ("date", "📅"),
+    ("disappointed", "😞"),
+    ("dizzy", "💫"),
+    ("dog", "🐶"),
+    ("dollar", "💵"),
+    ("door", "🚪"),
+    ("dragon", "🐉"),
+    ("droplet", "💧"),
+    ("earth_africa", "🌍"),
+    ("earth_americas", "🌎"),
+    ("earth_asia", "🌏"),
+    ("egg", "🥚"),
+    ("eight", "8️⃣"),
+    ("envelope", "✉️"),
+    ("exclamation", "❗"),
+    ("expressionless", "😑"),
+    ("eyes", "👀"),
+    ("facepalm", "🤦"),
+    ("fast_forward", "⏩"),
+    ("fearful", "😨"),
+    ("file_folder", "📁"),
+    ("fire", "🔥"),
+    ("fireworks", "🎆"),
+    ("fish", "🐟"),
+    ("five", "5️⃣"),
+    ("flashlight", "🔦"),
+    ("floppy_disk", "💾"),
+    ("flushed", "😳"),
+    ("four", "4️⃣"),
+    ("four_leaf_clover", "🍀"),
+    ("gear", "⚙️"),
+    ("gem", "💎"),
+    ("ghost", "👻"),
+    ("gift", "🎁"),
+    ("globe_with_meridians", "🌐"),
+    ("goal_net", "🥅"),
+    ("green_heart", "💚"),
+    ("grey_exclamation", "❕"),
+    ("grey_question", "❔"),
+    ("grimacing", "😬"),
+    ("grin", "😁"),
+    ("grinning", "😀"),
+    ("hammer", "🔨"),
+    ("hammer_and_pick", "⚒️"),
+    ("hammer_and_wrench", "🛠️"),
+    ("hand", "✋"),
+    ("handshake", "🤝"),
+    ("hankey", "💩"),
+    ("hash", "#️⃣"),
+    ("hatching_chick", "🐣"),
+    ("headphones", "🎧"),
+    ("heart", "❤️"),
+    ("heart_eyes", "😍"),
+    ("heavy_check_mark", "✔️"),
+    ("heavy_exclamation_mark", "❗"),
+    ("heavy_minus_sign", "➖"),
+    ("heavy_multiplication_x", "✖️"),
+    ("heavy_plus_sign", "➕"),
+    ("hibiscus", "🌺"),
+    ("high_brightness", "🔆"),
+    ("hocho", "🔪"),
+    ("honeybee", "🐝"),
+    ("hook", "🪝"),
+    ("hot_pepper", "🌶️"),
+    ("hourglass", "⌛"),
+    ("hourglass_flowing_sand", "⏳"),
+    ("house", "🏠"),
+    ("hugs", "🤗"),
+    ("hushed", "😯"),
+    ("id", "🆔"),
+    ("inbox_tray", "📥"),
+    ("incoming_envelope", "📨"),
+    ("information_source", "ℹ️"),
+    ("innocent", "😇"),
+    ("jack_o_lantern", "🎃"),
+    ("joy", "😂"),
+    ("key", "🔑"),
+    ("keyboard", "⌨️"),
+    ("kiss", "💋"),
+    ("kissing", "😗"),
+    ("label", "🏷️"),
+    ("lady_beetle", "🐞"),
+    ("ladybug", "🐞"),
+    ("laughing", "😆"),
+    ("leaves", "🍃"),
+    ("ledger", "📒"),
+    ("link", "🔗"),
+    ("lipstick", "💄"),
+    ("lock", "🔒"),
+    ("loud_sound", "🔊"),
+    ("loudspeaker", "📢"),
+    ("love_letter", "💌"),
+    ("mag", "🔍"),
+    ("mag_right", "🔎"),
+    ("mailbox", "📫"),
+    ("memo", "📝"),
+    ("microphone", "🎤"),
+    ("microscope", "🔬"),
+    ("money_with_wings", "💸"),
+    ("moneybag", "💰"),
+    ("monkey", "🐒"),
+    ("moon", "🌔"),
+    ("mortar_board", "🎓"),
+    ("mouse", "🐭"),
+    ("muscle", "💪"),
+    ("mushroom", "🍄"),
+    ("musical_note", "🎵"),
+    ("mute", "🔇"),
+    ("nail_care", "💅"),
+    ("necktie", "👔"),
+    ("negative_squared_cross_mark", "❎"),
+    ("nerd_face", "🤓"),
+    ("neutral_face", "😐"),
+    ("new", "🆕"),
+    ("newspaper", "📰"),
+    ("nine", "9️⃣"),
+    ("no_entry", "⛔"),
+    ("no_entry_sign", "🚫"),
+    ("no_good", "🙅"),
+    ("no_mouth", "😶"),
+    ("nose", "👃"),
+    ("notebook", "📓"),
+    ("notes", "🎶"),
+    ("o", "⭕"),
+    ("ok", "🆗"),
+    ("ok_hand", "👌"),
+    ("one", "1️⃣"),
+    ("open_book", "📖"),
+    ("open_file_folder", "📂"),
+    ("open_mouth", "😮"),
+    ("outbox_tray", "📤"),
+    ("package", "📦"),
+    ("page_facing_up", "📄"),
+    ("page_with_curl", "📃"),
+    ("paintbrush", "🖌️"),
+    ("palm_tree", "🌴"),
+    ("paperclip", "📎"),
+    ("partying_face", "🥳"),
+    ("pencil", "📝"),
+    ("pencil2", "✏️"),
+    ("penguin", "🐧"),
+    ("pensive", "😔"),
+    ("persevere", "😣"),
+    ("phone", "☎️"),
+    ("pig", "🐷"),
+    ("pill", "💊"),
+    ("pin", "📍"),
+    ("pizza", "🍕"),
+    ("point_down", "👇"),
+    ("point_left", "👈"),
+    ("point_right", "👉"),
+    ("point_up", "☝️"),
+    ("point_up_2", "👆"),
+    ("poop", "💩"),
+    ("popcorn", "🍿"),
+    ("pray", "🙏"),
+    ("pushpin", "📌"),
+    ("question", "❓"),
+    ("rabbit", "🐰"),
+    ("rainbow", "🌈"),
+    ("raised_hands", "🙌"),
+    ("recycle", "♻️"),
+    ("red_circle", "🔴"),
+    ("registered", "®️"),
+    ("relaxed", "☺️"),
+    ("relieved", "😌"),
+    ("repeat", "🔁"),
+    ("rewind", "⏪"),
+    ("ribbon", "🎀"),
+    ("robot", "🤖"),
+    ("rocket", "🚀"),
+    ("rofl", "🤣"),
+    ("rose", "🌹"),
+    ("rotating_light", "🚨"),
+    ("round_pushpin", "📍"),
+    ("sassy_woman", "💁"),
+    ("scissors", "✂️"),
+    ("scream", "😱"),
+    ("see_no_evil", "🙈"),
+    ("seedling", "🌱"),
+    ("seven", "7️⃣"),
+    ("shield", "🛡️"),
+    ("shipit", "🐿️"),
+    ("shrug", "🤷"),
+    ("six", "6️⃣"),
+    ("skull", "💀"),
+    ("sleeping", "😴"),
+    ("sleepy", "😪"),
+    ("slightly_frowning_face", "🙁"),
+    ("slightly_smiling_face", "🙂"),
+    ("smile", "😄"),
+    ("smiley", "😃"),
+    ("smiling_imp", "😈"),
+    ("smirk", "😏"),
+    ("snail", "🐌"),
+    ("snake", "🐍"),
+    ("snowflake", "❄️"),
+    ("snowman", "⛄"),
+    ("sob", "😭"),
+    ("soccer", "⚽"),
+    ("sos", "🆘"),
+    ("sound", "🔉"),
+    ("sparkle", "❇️"),
+    ("sparkles", "✨"),
+    ("sparkling_heart", "💖"),
+    ("speech_balloon", "💬"),
+    ("star", "⭐"),
+    ("star2", "🌟"),
+    ("stars", "🌠"),
+    ("stop_sign", "🛑"),
+    ("stopwatch", "⏱️"),
+    ("sun_with_face", "🌞"),
+    ("sunflower", "🌻"),
+    ("sunglasses", "😎"),
+    ("sunny", "☀️"),
+    ("sweat", "😓"),
+    ("sweat_smile", "😅"),
+    ("tada", "🎉"),
+    ("tea", "🍵"),
+    ("test_tube", "🧪"),
+    ("thinking", "🤔"),
+    ("thought_balloon", "💭"),
+    ("three", "3️⃣"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("ticket", "🎫"),
+    ("tired_face", "😫"),
+    ("toilet", "🚽"),
+    ("tongue", "👅"),
+    ("toolbox", "🧰"),
+    ("tools", "🛠️"),
+    ("trophy", "🏆"),
+    ("truck", "🚚"),
+    ("turtle", "🐢"),
+    ("tv", "📺"),
+    ("twisted_rightwards_arrows", "🔀"),
+    ("two", "2️⃣"),
+    ("two_hearts", "💕"),
+    ("umbrella", "☔"),
+    ("unamused", "😒"),
+    ("unicorn", "🦄"),
+    ("unlock", "🔓"),
+    ("up", "🆙"),
+    ("v", "✌️"),
+    ("vertical_traffic_light", "🚦"),
+    ("video_camera", "📹"),
+    ("volcano", "🌋"),
+    ("warning", "⚠️"),
+    ("wastebasket", "🗑️"),
+    ("watch", "⌚"),
+    ("wave", "👋"),
+    ("weary", "😩"),
+    ("whale", "🐳"),
+    ("wheelchair", "♿"),
+    ("white_check_mark", "✅"),
+    ("white_circle", "⚪"),
+    ("wink", "😉"),
+    ("wolf", "🐺"),
+    ("worried", "😟"),
+    ("wrench", "🔧"),
+    ("x", "❌"),
+    ("yellow_heart", "💛"),
+    ("yum", "😋"),
+    ("zap", "⚡"),
+    ("zero", "0️⃣"),
+    ("zipper_mouth_face", "🤐"),
+    ("zzz", "💤"),
+];
+
+/// The emoji for a shortcode name, without the surrounding colons.
+pub fn shortcode(name: &str) -> Option<&'static str> {
+    SHORTCODES
+        .binary_search_by(|(candidate, _)| (*candidate).cmp(name))
+        .ok()
+        .map(|index| SHORTCODES[index].1)
+}
+
+/// Replace known `:shortcode:`s in `text` with their emoji. Unknown names
+/// are left as written.
+pub fn replace_shortcodes(text: &str) -> Cow<'_, str> {
+    if !text.contains(':') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::new();
+    let mut copied = 0;
+    let mut search = 0;
+    while let Some(open) = text[search..].find(':').map(|i| search + i) {
+        let name_start = open + 1;
+        let name_len = text[name_start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-')))
+            .unwrap_or(text.len() - name_start);
+        let close = name_start + name_len;
+        if name_len > 0
+            && text[close..].starts_with(':')
+            && let Some(emoji) = shortcode(&text[name_start..close])
+        {
+            out.push_str(&text[copied..open]);
+            out.push_str(emoji);
+            copied = close + 1;
+            search = close + 1;
+        } else {
+            // The closing colon of a non-shortcode may open the next one.
+            search = name_start;
+        }
+    }
+
+    if copied == 0 {
+        return Cow::Borrowed(text);
+    }
+    out.push_str(&text[copied..]);
+    Cow::Owned(out)
+}
+
+/// Byte ranges of emoji sequences in `text`: pictographs shown as emoji by
+/// default or followed by VS16, with their modifiers, ZWJ sequences, flags
+/// and keycaps. Symbols that default to text style (`⚠`, `⌘`) are left to
+/// the text fonts unless followed by VS16.
+pub fn emoji_runs(text: &str) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let mut after_joiner = false;
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        let continues =
+            runs.last().is_some_and(|run| run.end == i) && (after_joiner || is_emoji_component(c));
+        let starts =
+            has_emoji_presentation(c) || matches!(next, Some('\u{FE0F}') | Some('\u{20E3}'));
+        if continues {
+            runs.last_mut().unwrap().end = i + c.len_utf8();
+        } else if starts {
+            runs.push(i..i + c.len_utf8());
+        }
+        after_joiner = c == '\u{200D}' && (continues || starts);
+    }
+    runs
+}
+
+/// Characters that extend an emoji sequence rather than start one.
+fn is_emoji_component(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x200D | 0xFE0F | 0x20E3 | 0x1F3FB..=0x1F3FF | 0x1F1E6..=0x1F1FF | 0xE0020..=0xE007F
+    )
+}
+
+/// Whether `c` is drawn as an emoji without a variation selector
+/// (Unicode `Emoji_Presentation`).
+fn has_emoji_presentation(c: char) -> bool {
+    let code = c as u32;
+    if code >= 0x1F000 {
+        return crate::fonts::is_emoji(c) && !matches!(code, 0x1F3FB..=0x1F3FF);
+    }
+    matches!(
+        code,
+        0x231A..=0x231B
+            | 0x23E9..=0x23EC
+            | 0x23F0
+            | 0x23F3
+            | 0x25FD..=0x25FE
+            | 0x2614..=0x2615
+            | 0x2648..=0x2653
+            | 0x267F
+            | 0x2693
+            | 0x26A1
+            | 0x26AA..=0x26AB
+            | 0x26BD..=0x26BE
+            | 0x26C4..=0x26C5
+            | 0x26CE
+            | 0x26D4
+            | 0x26EA
+            | 0x26F2..=0x26F3
+            | 0x26F5
+            | 0x26FA
+            | 0x26FD
+            | 0x2705
+            | 0x270A..=0x270B
+            | 0x2728
+            | 0x274C
+            | 0x274E
+            | 0x2753..=0x2755
+            | 0x2757
+            | 0x2795..=0x2797
+            | 0x27B0
+            | 0x27BF
+            | 0x2B1B..=0x2B1C
+            | 0x2B50
+            | 0x2B55
+    )
+}
+
+/// Draw `text` into a standalone `width`×`height` SVG with the fonts and
+/// fallback order used for PNG and PDF export, the baseline `ascent` below
+/// the top. Glyphs become paths and images, so the result looks the same
+/// in every viewer. Returns `None` if no installed font covers the text.
+pub fn glyph_svg(
+    text: &str,
+    font_family: &str,
+    font_size: f32,
+    fill: &str,
+    width: f32,
+    height: f32,
+    ascent: f32,
+) -> Option<String> {
+    if width <= 0.0 || height <= 0.0 || !crate::fonts::has_glyphs(text, font_family) {
+        return None;
+    }
+
+    let mut svg = String::new();
+    write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.2}" height="{h:.2}" viewBox="0 0 {w:.2} {h:.2}"><text x="0" y="{:.2}" font-family="{}" font-size="{:.2}" fill="{}">{}</text></svg>"#,
+        ascent,
+        crate::xml::escape_xml(font_family),
+        font_size,
+        fill,
+        crate::xml::escape_xml(text),
+        w = width,
+        h = height,
+    )
+    .unwrap();
+
+    let tree = usvg::Tree::from_str(&svg, &crate::export::usvg_options()).ok()?;
+    if !tree.root().has_children() {
+        return None;
+    }
+    Some(tree.to_string(&usvg::WriteOptions::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcode_table_is_sorted_for_lookup() {
+        assert!(SHORTCODES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(shortcode("rocket"), Some("🚀"));
+        assert_eq!(shortcode("+1"), Some("👍"));
+        assert_eq!(shortcode("not_an_emoji"), None);
+    }
+
+    #[test]
+    fn replaces_known_shortcodes_only() {
+        assert_eq!(
+            replace_shortcodes(":rocket: Launch :warning: done :white_check_mark:"),
+            "🚀 Launch ⚠️ done ✅"
+        );
+        assert_eq!(
+            replace_shortcodes("at 10:30:45 and a:xy:tada:"),
+            "at 10:30:45 and a:xy🎉"
+        );
+        assert_eq!(replace_shortcodes(":unknown: ::"), ":unknown: ::");
+        assert!(matches!(replace_shortcodes("no codes"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn emoji_runs_cover_sequences_but_not_text_style_symbols() {
+        let text = "Go 🚀! ⚠ vs ⚠️ 👍🏽 👨‍👩‍👧 🇩🇪 1️⃣ ⌘";
+        let runs: Vec<&str> = emoji_runs(text).into_iter().map(|r| &text[r]).collect();
+        assert_eq!(runs, ["🚀", "⚠️", "👍🏽", "👨‍👩‍👧", "🇩🇪", "1️⃣"]);
+    }
+
+    #[test]
+    fn glyph_svg_escapes_the_family_name() {
+        // Any installed face covers "A"; the bundled `fonts/` has Fira Sans.
+        let svg = glyph_svg("A", "Fira \"Sans\" & Co", 16.0, "#000", 12.0, 20.0, 16.0);
+        assert!(svg.is_some());
+    }
+}
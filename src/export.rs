@@ -1,5 +1,9 @@
+use crate::fonts;
+use crate::pages::{Bookmark, LinkTag, PagedDocument};
 use resvg::usvg;
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
 use tiny_skia::{Pixmap, Transform};
 
 pub fn svg_to_png(svg: &str, scale: f32) -> Result<Vec<u8>, String> {
@@ -7,21 +11,8 @@ pub fn svg_to_png(svg: &str, scale: f32) -> Result<Vec<u8>, String> {
         return Err(format!("Invalid --png-scale value: {}", scale));
     }
 
-    let mut opts = usvg::Options::default();
-    {
-        let fontdb = opts.fontdb_mut();
-        fontdb.load_system_fonts();
-
-        let local_fonts = Path::new("fonts");
-        if local_fonts.is_dir() {
-            fontdb.load_fonts_dir(local_fonts);
-        }
-
-        configure_font_fallbacks(fontdb);
-    }
-
-    let tree =
-        usvg::Tree::from_str(svg, &opts).map_err(|e| format!("Failed to parse SVG: {}", e))?;
+    let tree = usvg::Tree::from_str(svg, &usvg_options())
+        .map_err(|e| format!("Failed to parse SVG: {}", e))?;
 
     let svg_width = (tree.size().width() * scale).ceil() as u32;
     let svg_height = (tree.size().height() * scale).ceil() as u32;
@@ -36,43 +27,438 @@ pub fn svg_to_png(svg: &str, scale: f32) -> Result<Vec<u8>, String> {
         .map_err(|e| format!("Failed to encode PNG: {}", e))
 }
 
+/// usvg options with the measurement font database and fallback order.
+pub(crate) fn usvg_options() -> usvg::Options<'static> {
+    let fallbacks = fonts::current_fallbacks();
+    usvg::Options {
+        fontdb: Arc::new(fonts::font_database()),
+        font_resolver: usvg::FontResolver {
+            select_font: usvg::FontResolver::default_font_selector(),
+            select_fallback: Box::new(move |c, exclude, db| {
+                fonts::select_fallback(&fallbacks, c, exclude, db)
+            }),
+        },
+        ..Default::default()
+    }
+}
+
 pub fn svg_to_pdf(svg: &str) -> Result<Vec<u8>, String> {
-    use svg2pdf::usvg::fontdb;
+    // Parse the SVG
+    let tree = svg2pdf::usvg::Tree::from_str(svg, &pdf_usvg_options())
+        .map_err(|e| format!("Failed to parse SVG: {}", e))?;
+
+    let page_options = svg2pdf::PageOptions::default();
 
-    // Configure font options
-    let mut fontdb = fontdb::Database::new();
-    fontdb.load_system_fonts();
+    svg2pdf::to_pdf(&tree, pdf_conversion_options(), page_options)
+        .map_err(|e| format!("Failed to convert SVG to PDF: {}", e))
+}
 
-    let local_fonts = Path::new("fonts");
-    if local_fonts.is_dir() {
-        fontdb.load_fonts_dir(local_fonts);
+/// Convert paginated documents into one PDF, their pages in order, with
+/// `bookmarks` as its outline. Each document is converted once and shared
+/// by its pages, which clip out their part of it. Links become link
+/// annotations: to anchors of the documents, or to URLs.
+pub fn paged_to_pdf(
+    documents: &[PagedDocument],
+    bookmarks: &[Bookmark],
+) -> Result<Vec<u8>, String> {
+    use pdf_writer::types::{ActionType, AnnotationType};
+    use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+
+    let options = pdf_usvg_options();
+    let mut next_ref = Ref::new(1);
+    let catalog_ref = next_ref.bump();
+    let page_tree_ref = next_ref.bump();
+    let mut pdf = Pdf::new();
+    let mut page_refs = Vec::new();
+    let mut page_heights = Vec::new();
+
+    let parsed = documents
+        .iter()
+        .map(|paged| parse_document(&paged.document, &options))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut first_pages = Vec::with_capacity(documents.len());
+    let mut count = 0;
+    for paged in documents {
+        first_pages.push(count);
+        count += paged.pages.len();
+    }
+    // The page an anchor is on and its distance from the page's top, in
+    // pixels; an empty anchor is the top of the document.
+    let locate = |document: usize, anchor: &str| -> Option<(usize, f32)> {
+        let paged = documents.get(document)?;
+        let y = if anchor.is_empty() {
+            paged.pages.first()?.range.start
+        } else {
+            *parsed[document].1.anchors.get(anchor)?
+        };
+        let index = paged
+            .pages
+            .iter()
+            .position(|page| y < page.range.end)
+            .unwrap_or(paged.pages.len().checked_sub(1)?);
+        let page = &paged.pages[index];
+        Some((
+            first_pages[document] + index,
+            page.top + (y - page.range.start).max(0.0),
+        ))
+    };
+    // Annotations are written once every page has its reference.
+    let mut annotations = Vec::new();
+
+    for (index, paged) in documents.iter().enumerate() {
+        let (tree, links) = &parsed[index];
+        let (document_ref, document_height) = embed_tree(&mut pdf, &mut next_ref, tree)?;
+        // SVG pixels are 1/96 inch; PDF points are 1/72 inch.
+        let width = paged.width * 0.75;
+        let height = paged.page_height * 0.75;
+        for page in &paged.pages {
+            let (frame_ref, _) = embed_svg(&mut pdf, &mut next_ref, &options, &page.frame)?;
+            let page_ref = next_ref.bump();
+            let content_ref = next_ref.bump();
+
+            let mut annotation_refs = Vec::new();
+            for (href, [x, y, w, h]) in &links.links {
+                let (top, bottom) = (y.max(page.range.start), (y + h).min(page.range.end));
+                if top >= bottom {
+                    continue;
+                }
+                let target = if let Some(anchor) = href.strip_prefix('#') {
+                    locate(index, anchor).map(|(page, y)| LinkTarget::Page(page, y))
+                } else if let Some((document, anchor)) = paged.link_targets.get(href) {
+                    locate(*document, anchor).map(|(page, y)| LinkTarget::Page(page, y))
+                } else if crate::links::has_scheme(href) {
+                    Some(LinkTarget::Uri(href.clone()))
+                } else {
+                    None
+                };
+                let Some(target) = target else {
+                    continue;
+                };
+                let to_page = |y: f32| height - (page.top + y - page.range.start) * 0.75;
+                let rect = Rect::new(x * 0.75, to_page(bottom), (x + w) * 0.75, to_page(top));
+                let annotation_ref = next_ref.bump();
+                annotation_refs.push(annotation_ref);
+                annotations.push((annotation_ref, rect, target));
+            }
+
+            let mut pdf_page = pdf.page(page_ref);
+            pdf_page.media_box(Rect::new(0.0, 0.0, width, height));
+            pdf_page.parent(page_tree_ref);
+            pdf_page.contents(content_ref);
+            if !annotation_refs.is_empty() {
+                pdf_page.annotations(annotation_refs);
+            }
+            let mut resources = pdf_page.resources();
+            let mut x_objects = resources.x_objects();
+            x_objects.pair(Name(b"F"), frame_ref);
+            x_objects.pair(Name(b"D"), document_ref);
+            x_objects.finish();
+            resources.finish();
+            pdf_page.finish();
+
+            let slice = (page.range.end - page.range.start) * 0.75;
+            let slice_top = height - page.top * 0.75;
+            let mut content = Content::new();
+            content
+                .save_state()
+                .transform([width, 0.0, 0.0, height, 0.0, 0.0])
+                .x_object(Name(b"F"))
+                .restore_state();
+            content
+                .save_state()
+                .rect(0.0, slice_top - slice, width, slice)
+                .clip_nonzero()
+                .end_path()
+                .transform([
+                    width,
+                    0.0,
+                    0.0,
+                    document_height * 0.75,
+                    0.0,
+                    slice_top + (page.range.start - document_height) * 0.75,
+                ])
+                .x_object(Name(b"D"))
+                .restore_state();
+            pdf.stream(content_ref, &content.finish());
+            page_refs.push(page_ref);
+            page_heights.push(height);
+        }
     }
 
-    configure_font_fallbacks_svg2pdf(&mut fontdb);
+    for (annotation_ref, rect, target) in annotations {
+        let mut annotation = pdf.annotation(annotation_ref);
+        annotation
+            .subtype(AnnotationType::Link)
+            .rect(rect)
+            .border(0.0, 0.0, 0.0, None);
+        let mut action = annotation.action();
+        match target {
+            LinkTarget::Page(page, y) => {
+                action
+                    .action_type(ActionType::GoTo)
+                    .destination()
+                    .page(page_refs[page])
+                    .xyz(0.0, page_heights[page] - y * 0.75, None);
+            }
+            LinkTarget::Uri(uri) => {
+                action.action_type(ActionType::Uri).uri(Str(uri.as_bytes()));
+            }
+        }
+    }
 
-    let opts = svg2pdf::usvg::Options {
-        fontdb: std::sync::Arc::new(fontdb),
-        ..Default::default()
+    let bookmarks: Vec<&Bookmark> = bookmarks
+        .iter()
+        .filter(|bookmark| bookmark.page < page_refs.len())
+        .collect();
+    let mut catalog = pdf.catalog(catalog_ref);
+    catalog.pages(page_tree_ref);
+    if !bookmarks.is_empty() {
+        let outline_ref = next_ref.bump();
+        catalog
+            .outlines(outline_ref)
+            .page_mode(pdf_writer::types::PageMode::UseOutlines);
+        catalog.finish();
+        write_outline(
+            &mut pdf,
+            &mut next_ref,
+            outline_ref,
+            &bookmarks,
+            |bookmark| {
+                let page = bookmark.page;
+                (page_refs[page], page_heights[page] - bookmark.y * 0.75)
+            },
+        );
+    } else {
+        catalog.finish();
+    }
+    pdf.pages(page_tree_ref)
+        .count(page_refs.len() as i32)
+        .kids(page_refs);
+    Ok(pdf.finish())
+}
+
+/// Write `bookmarks` as the outline tree at `outline_ref`: each one nests
+/// under the closest earlier one of a lower level. `target` gives the page
+/// and the height on it in points that a bookmark jumps to.
+fn write_outline(
+    pdf: &mut pdf_writer::Pdf,
+    next_ref: &mut pdf_writer::Ref,
+    outline_ref: pdf_writer::Ref,
+    bookmarks: &[&Bookmark],
+    target: impl Fn(&Bookmark) -> (pdf_writer::Ref, f32),
+) {
+    use pdf_writer::TextStr;
+
+    let refs: Vec<_> = bookmarks.iter().map(|_| next_ref.bump()).collect();
+    // `children[0]` are the top-level items; `children[i + 1]` are item i's.
+    let mut children = vec![Vec::new(); bookmarks.len() + 1];
+    let mut parents = Vec::with_capacity(bookmarks.len());
+    let mut open: Vec<usize> = Vec::new();
+    for (index, bookmark) in bookmarks.iter().enumerate() {
+        while open
+            .last()
+            .is_some_and(|&last| bookmarks[last].level >= bookmark.level)
+        {
+            open.pop();
+        }
+        let parent = open.last().map_or(0, |&last| last + 1);
+        children[parent].push(index);
+        parents.push(parent);
+        open.push(index);
+    }
+    // Items are in document order, so an item's descendants follow it.
+    let descendants = |index: usize| {
+        bookmarks[index + 1..]
+            .iter()
+            .take_while(|bookmark| bookmark.level > bookmarks[index].level)
+            .count() as i32
     };
 
-    // Parse the SVG
-    let tree = svg2pdf::usvg::Tree::from_str(svg, &opts)
+    let top = &children[0];
+    pdf.outline(outline_ref)
+        .first(refs[top[0]])
+        .last(refs[top[top.len() - 1]])
+        .count(bookmarks.len() as i32);
+    for (index, bookmark) in bookmarks.iter().enumerate() {
+        let parent = parents[index];
+        let siblings = &children[parent];
+        let position = siblings.iter().position(|&i| i == index).unwrap_or(0);
+        let mut item = pdf.outline_item(refs[index]);
+        item.title(TextStr(&bookmark.title));
+        item.parent(if parent == 0 {
+            outline_ref
+        } else {
+            refs[parent - 1]
+        });
+        if position > 0 {
+            item.prev(refs[siblings[position - 1]]);
+        }
+        if let Some(&next) = siblings.get(position + 1) {
+            item.next(refs[next]);
+        }
+        if let (Some(&first), Some(&last)) =
+            (children[index + 1].first(), children[index + 1].last())
+        {
+            item.first(refs[first]).last(refs[last]);
+            item.count(descendants(index));
+        }
+        let (page, y) = target(bookmark);
+        item.dest().page(page).xyz(0.0, y, None);
+    }
+}
+
+/// Where a link annotation leads.
+enum LinkTarget {
+    /// A page, and a distance from its top in pixels.
+    Page(usize, f32),
+    Uri(String),
+}
+
+/// The links and anchors drawn in a document, in its coordinates.
+#[derive(Default)]
+struct DocumentLinks {
+    /// Each link's href and area as `[x, y, width, height]`.
+    links: Vec<(String, [f32; 4])>,
+    /// The top of each anchor, by id.
+    anchors: HashMap<String, f32>,
+}
+
+/// Parse `svg` for embedding and find the area of every link and anchor in
+/// it. Each `<a>` is given an id of its own first, so its group can be
+/// found in the parsed tree.
+fn parse_document(
+    svg: &str,
+    options: &svg2pdf::usvg::Options,
+) -> Result<(svg2pdf::usvg::Tree, DocumentLinks), String> {
+    let tags = crate::pages::link_tags(svg);
+    let mut tagged = String::with_capacity(svg.len());
+    let mut pos = 0;
+    for (index, tag) in tags.iter().enumerate() {
+        tagged.push_str(&svg[pos..tag.range.start]);
+        tagged.push_str(&format!(r#"<a id="markie-link-{}">"#, index));
+        pos = tag.range.end;
+    }
+    tagged.push_str(&svg[pos..]);
+
+    let tree = svg2pdf::usvg::Tree::from_str(&tagged, options)
         .map_err(|e| format!("Failed to parse SVG: {}", e))?;
+    let mut links = DocumentLinks::default();
+    collect_links(tree.root(), &tags, &mut links);
+    Ok((tree, links))
+}
 
-    // Convert to PDF.
+fn collect_links(group: &svg2pdf::usvg::Group, tags: &[LinkTag], links: &mut DocumentLinks) {
+    for node in group.children() {
+        let svg2pdf::usvg::Node::Group(child) = node else {
+            continue;
+        };
+        let tag = child
+            .id()
+            .strip_prefix("markie-link-")
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| tags.get(index));
+        if let Some(tag) = tag {
+            let bbox = child.abs_bounding_box();
+            if let Some(href) = &tag.href {
+                links.links.push((
+                    href.clone(),
+                    [bbox.x(), bbox.y(), bbox.width(), bbox.height()],
+                ));
+            }
+            if let Some(id) = &tag.id {
+                links.anchors.entry(id.clone()).or_insert(bbox.y());
+            }
+        }
+        collect_links(child, tags, links);
+    }
+}
+
+/// Add `svg` to `pdf` as a form XObject drawn in the unit square, numbering
+/// its objects from `next_ref`. Returns the XObject and the SVG's height.
+fn embed_svg(
+    pdf: &mut pdf_writer::Pdf,
+    next_ref: &mut pdf_writer::Ref,
+    options: &svg2pdf::usvg::Options,
+    svg: &str,
+) -> Result<(pdf_writer::Ref, f32), String> {
+    let tree = svg2pdf::usvg::Tree::from_str(svg, options)
+        .map_err(|e| format!("Failed to parse SVG: {}", e))?;
+    embed_tree(pdf, next_ref, &tree)
+}
+
+/// Add a parsed SVG to `pdf` like [`embed_svg`].
+fn embed_tree(
+    pdf: &mut pdf_writer::Pdf,
+    next_ref: &mut pdf_writer::Ref,
+    tree: &svg2pdf::usvg::Tree,
+) -> Result<(pdf_writer::Ref, f32), String> {
+    let (chunk, svg_ref) = svg2pdf::to_chunk(tree, pdf_conversion_options())
+        .map_err(|e| format!("Failed to convert SVG to PDF: {}", e))?;
+    let mut refs = HashMap::new();
+    let chunk = chunk.renumber(|old| *refs.entry(old).or_insert_with(|| next_ref.bump()));
+    pdf.extend(&chunk);
+    Ok((refs[&svg_ref], tree.size().height()))
+}
+
+fn pdf_usvg_options() -> svg2pdf::usvg::Options<'static> {
+    use svg2pdf::usvg as pdf_usvg;
+
+    // svg2pdf bundles an older usvg, but both share one fontdb, so the PDF
+    // path gets the same database and fallback order as measurement.
+    let fallbacks = fonts::current_fallbacks();
+    pdf_usvg::Options {
+        fontdb: Arc::new(fonts::font_database()),
+        font_resolver: pdf_usvg::FontResolver {
+            select_font: pdf_usvg::FontResolver::default_font_selector(),
+            select_fallback: Box::new(move |c, exclude, db| {
+                fonts::select_fallback(&fallbacks, c, exclude, db)
+            }),
+        },
+        ..Default::default()
+    }
+}
+
+fn pdf_conversion_options() -> svg2pdf::ConversionOptions {
     // Keep text as paths for broader viewer/font compatibility.
     // This avoids PDFs with missing text when font embedding fails.
-    let options = svg2pdf::ConversionOptions {
+    svg2pdf::ConversionOptions {
         embed_text: false,
         ..Default::default()
-    };
-    let page_options = svg2pdf::PageOptions::default();
+    }
+}
 
-    svg2pdf::to_pdf(&tree, options, page_options)
-        .map_err(|e| format!("Failed to convert SVG to PDF: {}", e))
+/// Output settings that apply after the SVG has been rendered.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Raster scale multiplier for PNG output.
+    pub png_scale: f32,
+    /// Embed subsetted fonts into SVG output (ignored for PNG and PDF, which
+    /// are rasterized or converted to paths with the local fonts).
+    pub embed_fonts: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            png_scale: 1.0,
+            embed_fonts: false,
+        }
+    }
 }
 
 pub fn save_output(svg: &str, output: &Path, png_scale: f32) -> Result<(), String> {
+    let options = ExportOptions {
+        png_scale,
+        ..Default::default()
+    };
+    save_output_with_options(svg, output, &options)
+}
+
+pub fn save_output_with_options(
+    svg: &str,
+    output: &Path,
+    options: &ExportOptions,
+) -> Result<(), String> {
     let output_ext = output
         .extension()
         .and_then(|e| e.to_str())
@@ -81,11 +467,16 @@ pub fn save_output(svg: &str, output: &Path, png_scale: f32) -> Result<(), Strin
 
     match output_ext.as_str() {
         "svg" => {
+            let svg = if options.embed_fonts {
+                crate::embed::embed_fonts(svg)?
+            } else {
+                svg.to_string()
+            };
             std::fs::write(output, svg).map_err(|e| format!("Failed to write SVG: {}", e))?;
             eprintln!("SVG saved to: {}", output.display());
         }
         "png" => {
-            let png_data = svg_to_png(svg, png_scale)?;
+            let png_data = svg_to_png(svg, options.png_scale)?;
             std::fs::write(output, png_data).map_err(|e| format!("Failed to write PNG: {}", e))?;
             eprintln!("PNG saved to: {}", output.display());
         }
@@ -105,71 +496,68 @@ pub fn save_output(svg: &str, output: &Path, png_scale: f32) -> Result<(), Strin
     Ok(())
 }
 
-/// Pick sans/serif/mono fallback families from a list of `(family_name, lowercased_name)` pairs.
-fn pick_fallback_families<'a>(
-    families: impl Iterator<Item = &'a (String, String)>,
-) -> (Option<String>, Option<String>, Option<String>) {
-    let mut sans_family: Option<String> = None;
-    let mut serif_family: Option<String> = None;
-    let mut mono_family: Option<String> = None;
-    let mut first_family: Option<String> = None;
-
-    for (family, lower) in families {
-        if first_family.is_none() {
-            first_family = Some(family.clone());
-        }
+/// Save paginated output: one PDF with every page of `documents`, or one
+/// numbered SVG or PNG file per page (`report-1.png`, `report-2.png`, ...).
+/// `bookmarks` become the PDF's outline.
+pub fn save_pages(
+    documents: &[PagedDocument],
+    bookmarks: &[Bookmark],
+    output: &Path,
+    options: &ExportOptions,
+) -> Result<(), String> {
+    let output_ext = output
+        .extension()
+        .and_then(|e| e.to_str())
+        .ok_or("Output file has no extension")?
+        .to_ascii_lowercase();
 
-        if sans_family.is_none() && lower.contains("sans") {
-            sans_family = Some(family.clone());
-        }
-        if serif_family.is_none() && lower.contains("serif") {
-            serif_family = Some(family.clone());
-        }
-        if mono_family.is_none() && (lower.contains("mono") || lower.contains("code")) {
-            mono_family = Some(family.clone());
-        }
+    if output_ext == "pdf" {
+        let pdf_data = paged_to_pdf(documents, bookmarks)?;
+        std::fs::write(output, pdf_data).map_err(|e| format!("Failed to write PDF: {}", e))?;
+        eprintln!(
+            "PDF saved to: {} ({} pages)",
+            output.display(),
+            documents
+                .iter()
+                .map(|paged| paged.pages.len())
+                .sum::<usize>()
+        );
+        return Ok(());
     }
-
-    (sans_family, serif_family, mono_family)
-}
-
-/// Configure generic-family fallbacks on a font database. The resvg (PNG) and
-/// svg2pdf (PDF) paths pull in different usvg versions, so their
-/// `fontdb::Database` types are unrelated and can't be unified through a shared
-/// function or trait — hence a macro.
-macro_rules! configure_font_fallbacks {
-    ($fontdb:expr) => {{
-        let families: Vec<(String, String)> = $fontdb
-            .faces()
-            .flat_map(|face| &face.families)
-            .map(|(family, _)| (family.clone(), family.to_ascii_lowercase()))
-            .collect();
-
-        let (sans_family, serif_family, mono_family) = pick_fallback_families(families.iter());
-        let first_family = families.first().map(|(f, _)| f.clone());
-
-        if let Some(family) = sans_family.as_deref().or(first_family.as_deref()) {
-            $fontdb.set_sans_serif_family(family);
-        }
-        if let Some(family) = serif_family.as_deref().or(first_family.as_deref()) {
-            $fontdb.set_serif_family(family);
-        }
-        if let Some(family) = mono_family
-            .as_deref()
-            .or(sans_family.as_deref())
-            .or(first_family.as_deref())
-        {
-            $fontdb.set_monospace_family(family);
-        }
-    }};
+    let pages: Vec<String> = documents
+        .iter()
+        .flat_map(PagedDocument::page_svgs)
+        .collect();
+    for (page, path) in pages.iter().zip(page_paths(output, pages.len())) {
+        save_output_with_options(page, &path, options)?;
+    }
+    Ok(())
 }
 
-fn configure_font_fallbacks(fontdb: &mut usvg::fontdb::Database) {
-    configure_font_fallbacks!(fontdb);
+/// The files [`save_pages`] writes `count` SVG or PNG pages to: `output`
+/// itself for a single page, otherwise numbered files.
+pub fn page_paths(output: &Path, count: usize) -> Vec<std::path::PathBuf> {
+    if count == 1 {
+        return vec![output.to_path_buf()];
+    }
+    (1..=count)
+        .map(|number| numbered_path(output, number, count))
+        .collect()
 }
 
-fn configure_font_fallbacks_svg2pdf(fontdb: &mut svg2pdf::usvg::fontdb::Database) {
-    configure_font_fallbacks!(fontdb);
+/// `dir/name-N.ext`, with N zero-padded to the width of `count`.
+pub fn numbered_path(output: &Path, number: usize, count: usize) -> std::path::PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let digits = count.to_string().len();
+    let mut name = format!("{}-{:0digits$}", stem, number, digits = digits);
+    if let Some(ext) = output.extension() {
+        name.push('.');
+        name.push_str(&ext.to_string_lossy());
+    }
+    output.with_file_name(name)
 }
 
 #[cfg(test)]
@@ -192,4 +580,64 @@ mod tests {
                 .contains("Unsupported output format: .txt")
         );
     }
+
+    #[test]
+    fn test_paged_pdf_links_lead_to_anchor_pages_and_urls() {
+        let document = |body: &str| {
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="400" viewBox="0 0 100 400">{}</svg>"#,
+                body
+            )
+        };
+        let page = |range: std::ops::Range<f32>| crate::pages::Page {
+            range,
+            top: 0.0,
+            frame: document(""),
+        };
+        let mut first = PagedDocument::single_page(
+            100.0,
+            200.0,
+            document(concat!(
+                r#"<a href="other.md#far"><rect x="10" y="20" width="30" height="10" /></a>"#,
+                r#"<a href="https://e.com/?a=1&amp;b=2"><rect x="10" y="40" width="30" height="10" /></a>"#,
+                r##"<a href="#missing"><rect x="10" y="60" width="30" height="10" /></a>"##,
+            )),
+            "#fff",
+        );
+        first
+            .link_targets
+            .insert("other.md#far".to_string(), (1, "far".to_string()));
+        let second = PagedDocument {
+            pages: vec![page(0.0..200.0), page(200.0..400.0)],
+            ..PagedDocument::single_page(
+                100.0,
+                200.0,
+                document(r#"<a id="far"><rect x="0" y="300" width="50" height="20" /></a>"#),
+                "#fff",
+            )
+        };
+
+        let (_, links) = parse_document(&second.document, &pdf_usvg_options()).unwrap();
+        assert_eq!(links.anchors["far"], 300.0);
+
+        let pdf =
+            String::from_utf8_lossy(&paged_to_pdf(&[first, second], &[]).unwrap()).into_owned();
+        assert_eq!(pdf.matches("/Subtype /Link").count(), 2, "{pdf}");
+        assert!(pdf.contains("/URI (https://e.com/?a=1&b=2)"));
+        // The anchor is 100px into the third page, which is 150pt tall.
+        let goto = pdf.split("/S /GoTo").nth(1).unwrap();
+        assert!(goto.contains("/XYZ 0 75"), "{goto}");
+    }
+
+    #[test]
+    fn test_numbered_page_paths() {
+        assert_eq!(
+            numbered_path(Path::new("out/report.png"), 3, 12),
+            Path::new("out/report-03.png")
+        );
+        assert_eq!(
+            numbered_path(Path::new("deck.svg"), 2, 5),
+            Path::new("deck-2.svg")
+        );
+    }
 }
@@ -1,18 +1,189 @@
+use std::collections::{BTreeSet, HashMap};
 use std::num::NonZeroUsize;
-use std::sync::LazyLock;
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
 
-use cosmic_text::{Attrs, Buffer, Family, FontSystem, Metrics, Shaping, Style, Weight};
+use cosmic_text::fontdb::{Database, ID};
+use cosmic_text::{
+    Attrs, Buffer, Fallback, Family, FontSystem, Metrics, PlatformFallback, Shaping, Stretch,
+    Style, Weight,
+};
 use lru::LruCache;
 use parking_lot::Mutex;
+use unicode_script::{Script, UnicodeScript};
 
-#[derive(Hash, PartialEq, Eq, Clone)]
-struct MeasureKey {
-    text: String,
-    font_size_bits: u32,
-    is_code: bool,
-    is_bold: bool,
-    is_italic: bool,
-    max_width_bits: Option<u32>,
+use crate::theme::FontFallbacks;
+
+/// Version of the [`TextMeasure`] API.
+///
+/// - 1: `measure_text` with `is_code`/`is_bold`/`is_italic` flags, returning
+///   `(width, height)`.
+/// - 2: [`TextMeasure::measure`] and [`TextMeasure::font_metrics`] taking a
+///   [`FontDescriptor`] and returning real font metrics. Both have default
+///   implementations on top of version 1 (with estimated 0.8/0.2 ascent and
+///   descent), so existing implementations keep working unchanged.
+pub const TEXT_MEASURE_API_VERSION: u32 = 2;
+
+/// Generic or named font family.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum FontFamily {
+    #[default]
+    SansSerif,
+    Serif,
+    Monospace,
+    Named(String),
+}
+
+impl FontFamily {
+    /// The family a CSS `font-family` name stands for.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "sans-serif" => Self::SansSerif,
+            "serif" => Self::Serif,
+            "monospace" => Self::Monospace,
+            name => Self::Named(name.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+/// Font width, as the CSS `font-stretch` keywords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FontStretch {
+    UltraCondensed,
+    ExtraCondensed,
+    Condensed,
+    SemiCondensed,
+    #[default]
+    Normal,
+    SemiExpanded,
+    Expanded,
+    ExtraExpanded,
+    UltraExpanded,
+}
+
+/// Which font to measure with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FontDescriptor {
+    pub family: FontFamily,
+    /// CSS weight (400 regular, 700 bold).
+    pub weight: u16,
+    pub style: FontStyle,
+    pub stretch: FontStretch,
+}
+
+impl Default for FontDescriptor {
+    fn default() -> Self {
+        Self {
+            family: FontFamily::SansSerif,
+            weight: 400,
+            style: FontStyle::Normal,
+            stretch: FontStretch::Normal,
+        }
+    }
+}
+
+impl FontDescriptor {
+    /// The descriptor equivalent to the version 1 measurement flags.
+    pub fn from_flags(is_code: bool, is_bold: bool, is_italic: bool) -> Self {
+        Self {
+            family: if is_code {
+                FontFamily::Monospace
+            } else {
+                FontFamily::SansSerif
+            },
+            weight: if is_bold { 700 } else { 400 },
+            style: if is_italic {
+                FontStyle::Italic
+            } else {
+                FontStyle::Normal
+            },
+            stretch: FontStretch::Normal,
+        }
+    }
+
+    pub fn is_code(&self) -> bool {
+        self.family == FontFamily::Monospace
+    }
+
+    pub fn is_bold(&self) -> bool {
+        self.weight >= 600
+    }
+
+    pub fn is_italic(&self) -> bool {
+        self.style != FontStyle::Normal
+    }
+
+    fn attrs(&self) -> Attrs<'_> {
+        let family = match &self.family {
+            FontFamily::SansSerif => Family::SansSerif,
+            FontFamily::Serif => Family::Serif,
+            FontFamily::Monospace => Family::Monospace,
+            FontFamily::Named(name) => Family::Name(name),
+        };
+        let style = match self.style {
+            FontStyle::Normal => Style::Normal,
+            FontStyle::Italic => Style::Italic,
+            FontStyle::Oblique => Style::Oblique,
+        };
+        let stretch = match self.stretch {
+            FontStretch::UltraCondensed => Stretch::UltraCondensed,
+            FontStretch::ExtraCondensed => Stretch::ExtraCondensed,
+            FontStretch::Condensed => Stretch::Condensed,
+            FontStretch::SemiCondensed => Stretch::SemiCondensed,
+            FontStretch::Normal => Stretch::Normal,
+            FontStretch::SemiExpanded => Stretch::SemiExpanded,
+            FontStretch::Expanded => Stretch::Expanded,
+            FontStretch::ExtraExpanded => Stretch::ExtraExpanded,
+            FontStretch::UltraExpanded => Stretch::UltraExpanded,
+        };
+        Attrs::new()
+            .family(family)
+            .weight(Weight(self.weight))
+            .style(style)
+            .stretch(stretch)
+    }
+}
+
+/// Vertical metrics of a font at a given size, in pixels. `ascent` and
+/// `descent` are both positive distances from the baseline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+impl FontMetrics {
+    /// Typical proportions, for measurers without access to font files.
+    pub fn estimated(font_size: f32) -> Self {
+        Self {
+            ascent: font_size * 0.8,
+            descent: font_size * 0.2,
+            line_gap: 0.0,
+        }
+    }
+}
+
+/// Result of measuring a piece of text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMetrics {
+    /// Advance width of the widest line.
+    pub advance: f32,
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+    /// Total height of all lines.
+    pub height: f32,
+    /// Advance width of each line after wrapping to `max_width`.
+    pub line_widths: Vec<f32>,
 }
 
 pub trait TextMeasure {
@@ -25,6 +196,77 @@ pub trait TextMeasure {
         is_italic: bool,
         max_width: Option<f32>,
     ) -> (f32, f32);
+
+    /// Measure `text` set in `font` (API version 2). The default adapts
+    /// [`TextMeasure::measure_text`], reporting a single line.
+    fn measure(
+        &mut self,
+        text: &str,
+        font: &FontDescriptor,
+        font_size: f32,
+        max_width: Option<f32>,
+    ) -> TextMetrics {
+        let (advance, height) = self.measure_text(
+            text,
+            font_size,
+            font.is_code(),
+            font.is_bold(),
+            font.is_italic(),
+            max_width,
+        );
+        let metrics = self.font_metrics(font, font_size);
+        TextMetrics {
+            advance,
+            ascent: metrics.ascent,
+            descent: metrics.descent,
+            line_gap: metrics.line_gap,
+            height,
+            line_widths: vec![advance],
+        }
+    }
+
+    /// Vertical metrics of `font` at `font_size` (API version 2). The default
+    /// returns [`FontMetrics::estimated`].
+    fn font_metrics(&mut self, _font: &FontDescriptor, font_size: f32) -> FontMetrics {
+        FontMetrics::estimated(font_size)
+    }
+}
+
+/// Lets several renderers take turns with one measurer (e.g. one per slide).
+impl<T: TextMeasure + ?Sized> TextMeasure for &mut T {
+    fn measure_text(
+        &mut self,
+        text: &str,
+        font_size: f32,
+        is_code: bool,
+        is_bold: bool,
+        is_italic: bool,
+        max_width: Option<f32>,
+    ) -> (f32, f32) {
+        (**self).measure_text(text, font_size, is_code, is_bold, is_italic, max_width)
+    }
+
+    fn measure(
+        &mut self,
+        text: &str,
+        font: &FontDescriptor,
+        font_size: f32,
+        max_width: Option<f32>,
+    ) -> TextMetrics {
+        (**self).measure(text, font, font_size, max_width)
+    }
+
+    fn font_metrics(&mut self, font: &FontDescriptor, font_size: f32) -> FontMetrics {
+        (**self).font_metrics(font, font_size)
+    }
+}
+
+#[derive(Hash, PartialEq, Eq, Clone)]
+struct MeasureKey {
+    text: String,
+    font: FontDescriptor,
+    font_size_bits: u32,
+    max_width_bits: Option<u32>,
 }
 
 /// Global font system with thread-safe LRU cache.
@@ -36,78 +278,127 @@ static GLOBAL_FONT_SYSTEM: LazyLock<GlobalFontSystem> = LazyLock::new(GlobalFont
 
 struct GlobalFontSystem {
     font_system: Mutex<FontSystem>,
-    cache: Mutex<LruCache<MeasureKey, (f32, f32)>>,
+    fallbacks: Mutex<FontFallbacks>,
+    cache: Mutex<LruCache<MeasureKey, TextMetrics>>,
+    /// Unit-size vertical metrics of the face each descriptor resolves to.
+    font_metrics: Mutex<HashMap<FontDescriptor, FontMetrics>>,
 }
 
 impl GlobalFontSystem {
     fn new() -> Self {
         // Cache up to ~10MB of text measurements (estimated ~100 bytes per entry average)
         const CACHE_CAPACITY: usize = 100_000;
+        let fallbacks = FontFallbacks::default();
         Self {
-            font_system: Mutex::new(FontSystem::new()),
+            font_system: Mutex::new(build_font_system(&fallbacks)),
+            fallbacks: Mutex::new(fallbacks),
             cache: Mutex::new(LruCache::new(NonZeroUsize::new(CACHE_CAPACITY).unwrap())),
+            font_metrics: Mutex::new(HashMap::new()),
         }
     }
 
-    fn measure_text(
+    /// Rebuild the font system for a new set of fallbacks. Cached
+    /// measurements were made with the old chains, so they are dropped.
+    fn configure(&self, fallbacks: &FontFallbacks) {
+        let mut current = self.fallbacks.lock();
+        if *current == *fallbacks {
+            return;
+        }
+        *self.font_system.lock() = build_font_system(fallbacks);
+        *current = fallbacks.clone();
+        self.cache.lock().clear();
+        self.font_metrics.lock().clear();
+    }
+
+    fn measure(
         &self,
         text: &str,
+        font: &FontDescriptor,
         font_size: f32,
-        is_code: bool,
-        is_bold: bool,
-        is_italic: bool,
         max_width: Option<f32>,
-    ) -> (f32, f32) {
+    ) -> TextMetrics {
         let key = MeasureKey {
             text: text.to_string(),
+            font: font.clone(),
             font_size_bits: font_size.to_bits(),
-            is_code,
-            is_bold,
-            is_italic,
             max_width_bits: max_width.map(f32::to_bits),
         };
 
         // Check cache first
         {
             let mut cache = self.cache.lock();
-            if let Some(&cached) = cache.get(&key) {
-                return cached;
+            if let Some(cached) = cache.get(&key) {
+                return cached.clone();
             }
         }
 
+        let metrics = self.font_metrics(font, font_size);
+
         // Perform measurement with font_system lock
         let measured = {
             let mut font_system = self.font_system.lock();
-            measure_text_impl(
-                &mut font_system,
-                text,
-                font_size,
-                is_code,
-                is_bold,
-                is_italic,
-                max_width,
-            )
+            measure_text_impl(&mut font_system, text, font, font_size, max_width, metrics)
         };
 
         // Store in cache
         {
             let mut cache = self.cache.lock();
-            cache.put(key, measured);
+            cache.put(key, measured.clone());
         }
 
         measured
     }
+
+    fn font_metrics(&self, font: &FontDescriptor, font_size: f32) -> FontMetrics {
+        let unit = {
+            let cached = self.font_metrics.lock().get(font).copied();
+            match cached {
+                Some(unit) => unit,
+                None => {
+                    let unit = {
+                        let mut font_system = self.font_system.lock();
+                        unit_font_metrics(&mut font_system, font)
+                    }
+                    .unwrap_or_else(|| FontMetrics::estimated(1.0));
+                    self.font_metrics.lock().insert(font.clone(), unit);
+                    unit
+                }
+            }
+        };
+        FontMetrics {
+            ascent: unit.ascent * font_size,
+            descent: unit.descent * font_size,
+            line_gap: unit.line_gap * font_size,
+        }
+    }
+}
+
+/// Per-em vertical metrics of the face cosmic-text picks first for `font`.
+fn unit_font_metrics(font_system: &mut FontSystem, font: &FontDescriptor) -> Option<FontMetrics> {
+    // Shape a sample so the face is the one measurement actually uses.
+    let mut buffer = Buffer::new(font_system, Metrics::new(16.0, 16.0 * 1.2));
+    buffer.set_text(font_system, "Hg", &font.attrs(), Shaping::Advanced, None);
+    buffer.shape_until_scroll(font_system, false);
+    let id = buffer.layout_runs().next()?.glyphs.first()?.font_id;
+    font_system.db().with_face_data(id, |data, index| {
+        let face = ttf_parser::Face::parse(data, index).ok()?;
+        let units = face.units_per_em() as f32;
+        Some(FontMetrics {
+            ascent: face.ascender() as f32 / units,
+            descent: -(face.descender() as f32) / units,
+            line_gap: face.line_gap() as f32 / units,
+        })
+    })?
 }
 
 fn measure_text_impl(
     font_system: &mut FontSystem,
     text: &str,
+    font: &FontDescriptor,
     font_size: f32,
-    is_code: bool,
-    is_bold: bool,
-    is_italic: bool,
     max_width: Option<f32>,
-) -> (f32, f32) {
+    metrics: FontMetrics,
+) -> TextMetrics {
     let line_height = font_size * 1.2;
     let mut buffer = Buffer::new(
         font_system,
@@ -118,35 +409,176 @@ fn measure_text_impl(
     );
 
     buffer.set_size(font_system, max_width, None);
-
-    let attrs = Attrs::new()
-        .family(if is_code {
-            Family::Monospace
-        } else {
-            Family::SansSerif
-        })
-        .weight(if is_bold {
-            Weight::BOLD
-        } else {
-            Weight::NORMAL
-        })
-        .style(if is_italic {
-            Style::Italic
-        } else {
-            Style::Normal
-        });
-
-    buffer.set_text(font_system, text, &attrs, Shaping::Advanced, None);
+    buffer.set_text(font_system, text, &font.attrs(), Shaping::Advanced, None);
 
     let mut total_width: f32 = 0.0;
     let mut total_height: f32 = 0.0;
+    let mut line_widths = Vec::new();
 
     for run in buffer.layout_runs() {
         total_width = total_width.max(run.line_w);
         total_height += run.line_height;
+        line_widths.push(run.line_w);
+    }
+
+    TextMetrics {
+        advance: total_width,
+        ascent: metrics.ascent,
+        descent: metrics.descent,
+        line_gap: metrics.line_gap,
+        height: total_height,
+        line_widths,
+    }
+}
+
+/// Font selection of a run of SVG text, as written by the renderer: a generic
+/// family name (`sans-serif`, `serif`, `monospace`), a numeric weight and slant.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct FontKey {
+    pub family: String,
+    pub weight: u16,
+    pub italic: bool,
+}
+
+impl FontKey {
+    fn attrs(&self) -> Attrs<'_> {
+        let family = match self.family.as_str() {
+            "monospace" => Family::Monospace,
+            "serif" => Family::Serif,
+            _ => Family::SansSerif,
+        };
+        Attrs::new()
+            .family(family)
+            .weight(Weight(self.weight))
+            .style(if self.italic {
+                Style::Italic
+            } else {
+                Style::Normal
+            })
     }
+}
 
-    (total_width, total_height)
+/// A font face cosmic-text picked while shaping text, with the characters it covered.
+pub struct ResolvedFace {
+    /// Raw font file data (the whole collection for `.ttc` files).
+    pub data: Vec<u8>,
+    /// Face index within `data`.
+    pub index: u32,
+    pub weight: u16,
+    pub italic: bool,
+    pub chars: BTreeSet<char>,
+}
+
+/// Faces resolved for a set of text runs. `chains` lists, per font key, the
+/// indices into `faces` in the order the shaper first used them.
+#[derive(Default)]
+pub struct ResolvedFonts {
+    pub faces: Vec<ResolvedFace>,
+    pub chains: HashMap<FontKey, Vec<usize>>,
+}
+
+/// Shape every run with the global font system and report which faces were
+/// used for which characters. This goes through the same font matching and
+/// fallback as measurement, so the result is exactly what was measured.
+pub fn resolve_faces<'a>(runs: impl IntoIterator<Item = (&'a FontKey, &'a str)>) -> ResolvedFonts {
+    let mut font_system = GLOBAL_FONT_SYSTEM.font_system.lock();
+    let mut resolved = ResolvedFonts::default();
+    let mut face_slots: HashMap<cosmic_text::fontdb::ID, usize> = HashMap::new();
+
+    for (key, text) in runs {
+        if text.trim().is_empty() {
+            continue;
+        }
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(16.0, 16.0 * 1.2));
+        buffer.set_size(&mut font_system, None, None);
+        buffer.set_text(
+            &mut font_system,
+            text,
+            &key.attrs(),
+            Shaping::Advanced,
+            None,
+        );
+        buffer.shape_until_scroll(&mut font_system, false);
+
+        let mut used: Vec<(cosmic_text::fontdb::ID, String)> = Vec::new();
+        for run in buffer.layout_runs() {
+            for glyph in run.glyphs {
+                let cluster = run.text.get(glyph.start..glyph.end).unwrap_or("");
+                used.push((glyph.font_id, cluster.to_string()));
+            }
+        }
+
+        for (font_id, cluster) in used {
+            let slot = match face_slots.get(&font_id) {
+                Some(&slot) => slot,
+                None => {
+                    let db = font_system.db();
+                    let Some(info) = db.face(font_id) else {
+                        continue;
+                    };
+                    let (weight, italic, index) = (
+                        info.weight.0,
+                        info.style != cosmic_text::fontdb::Style::Normal,
+                        info.index,
+                    );
+                    let Some(data) = db.with_face_data(font_id, |data, _| data.to_vec()) else {
+                        continue;
+                    };
+                    resolved.faces.push(ResolvedFace {
+                        data,
+                        index,
+                        weight,
+                        italic,
+                        chars: BTreeSet::new(),
+                    });
+                    let slot = resolved.faces.len() - 1;
+                    face_slots.insert(font_id, slot);
+                    slot
+                }
+            };
+
+            resolved.faces[slot]
+                .chars
+                .extend(cluster.chars().filter(|c| !c.is_control()));
+            let chain = resolved.chains.entry(key.clone()).or_default();
+            if !chain.contains(&slot) {
+                chain.push(slot);
+            }
+        }
+    }
+
+    resolved
+}
+
+/// Whether the global font system has a glyph for every character of
+/// `text` set in `family` (a `font-family` name), after fallback: shaping
+/// it produced no `.notdef` glyphs.
+pub fn has_glyphs(text: &str, family: &str) -> bool {
+    let font = FontDescriptor {
+        family: FontFamily::from_name(family),
+        ..FontDescriptor::default()
+    };
+    let mut font_system = GLOBAL_FONT_SYSTEM.font_system.lock();
+    let mut buffer = Buffer::new(&mut font_system, Metrics::new(16.0, 16.0 * 1.2));
+    buffer.set_size(&mut font_system, None, None);
+    buffer.set_text(
+        &mut font_system,
+        text,
+        &font.attrs(),
+        Shaping::Advanced,
+        None,
+    );
+    buffer.shape_until_scroll(&mut font_system, false);
+    let mut any = false;
+    for run in buffer.layout_runs() {
+        for glyph in run.glyphs {
+            if glyph.glyph_id == 0 {
+                return false;
+            }
+            any = true;
+        }
+    }
+    any
 }
 
 /// Text measurement using the global font system with LRU cache.
@@ -159,6 +591,304 @@ impl CosmicTextMeasure {
         let _ = &*GLOBAL_FONT_SYSTEM;
         Ok(Self)
     }
+
+    /// Like [`CosmicTextMeasure::new`], but first switches the global font
+    /// system to the given family preferences and fallback chains.
+    pub fn with_fallbacks(fallbacks: &FontFallbacks) -> Result<Self, String> {
+        configure_fallbacks(fallbacks);
+        Self::new()
+    }
+}
+
+/// Switch the global font system (used for measurement and, through
+/// [`font_database`], for rasterization) to the given font preferences.
+pub fn configure_fallbacks(fallbacks: &FontFallbacks) {
+    GLOBAL_FONT_SYSTEM.configure(fallbacks);
+}
+
+/// A copy of the font database used for measurement, with the same generic
+/// family assignments, for handing to resvg/svg2pdf.
+pub fn font_database() -> Database {
+    GLOBAL_FONT_SYSTEM.font_system.lock().db().clone()
+}
+
+/// The fallback configuration the global font system currently uses.
+pub fn current_fallbacks() -> FontFallbacks {
+    GLOBAL_FONT_SYSTEM.fallbacks.lock().clone()
+}
+
+/// Load system fonts plus any fonts in a local `fonts/` directory.
+fn load_font_database(db: &mut Database) {
+    db.load_system_fonts();
+
+    let local_fonts = Path::new("fonts");
+    if local_fonts.is_dir() {
+        db.load_fonts_dir(local_fonts);
+    }
+}
+
+fn build_font_system(fallbacks: &FontFallbacks) -> FontSystem {
+    // `FontSystem::new` detects the locale; reuse it with our own database.
+    let (locale, _) = FontSystem::new_with_fonts(std::iter::empty()).into_locale_and_db();
+    let mut db = Database::new();
+    load_font_database(&mut db);
+    apply_generic_families(&mut db, fallbacks);
+    FontSystem::new_with_locale_and_db_and_fallback(locale, db, ScriptFallback::new(fallbacks))
+}
+
+/// Assign the generic `sans-serif`/`serif`/`monospace` families: the first
+/// installed family from the theme's list, else cosmic-text's usual default
+/// when installed, else the first family whose name looks right.
+fn apply_generic_families(db: &mut Database, fallbacks: &FontFallbacks) {
+    let families: Vec<(String, String)> = db
+        .faces()
+        .flat_map(|face| &face.families)
+        .map(|(family, _)| (family.clone(), family.to_ascii_lowercase()))
+        .collect();
+    let installed = |name: &str| {
+        families
+            .iter()
+            .find(|(_, lower)| *lower == name.to_ascii_lowercase())
+            .map(|(family, _)| family.clone())
+    };
+    let first_installed = |names: &[String], default: &str| {
+        names
+            .iter()
+            .find_map(|n| installed(n))
+            .or_else(|| installed(default))
+    };
+
+    let (sans_guess, serif_guess, mono_guess) = pick_fallback_families(families.iter());
+    let first_family = families.first().map(|(f, _)| f.clone());
+
+    let sans = first_installed(&fallbacks.sans_serif, "Open Sans")
+        .or(sans_guess)
+        .or(first_family.clone());
+    let serif = first_installed(&fallbacks.serif, "DejaVu Serif")
+        .or(serif_guess)
+        .or(first_family.clone());
+    let mono = first_installed(&fallbacks.monospace, "Noto Sans Mono")
+        .or(mono_guess)
+        .or(sans.clone());
+
+    if let Some(family) = sans {
+        db.set_sans_serif_family(family);
+    }
+    if let Some(family) = serif {
+        db.set_serif_family(family);
+    }
+    if let Some(family) = mono {
+        db.set_monospace_family(family);
+    }
+}
+
+/// Pick sans/serif/mono fallback families from a list of `(family_name, lowercased_name)` pairs.
+fn pick_fallback_families<'a>(
+    families: impl Iterator<Item = &'a (String, String)>,
+) -> (Option<String>, Option<String>, Option<String>) {
+    let mut sans_family: Option<String> = None;
+    let mut serif_family: Option<String> = None;
+    let mut mono_family: Option<String> = None;
+
+    for (family, lower) in families {
+        if sans_family.is_none() && lower.contains("sans") {
+            sans_family = Some(family.clone());
+        }
+        if serif_family.is_none() && lower.contains("serif") {
+            serif_family = Some(family.clone());
+        }
+        if mono_family.is_none() && (lower.contains("mono") || lower.contains("code")) {
+            mono_family = Some(family.clone());
+        }
+    }
+
+    (sans_family, serif_family, mono_family)
+}
+
+/// Fallback-chain key for a character: `emoji` for pictographs, otherwise
+/// the lowercased Unicode script name (`han`, `arabic`, ...), with kana
+/// grouped as `japanese` and hangul as `korean`. Script-neutral characters
+/// (punctuation, digits) have no key.
+pub fn script_key(c: char) -> Option<String> {
+    if is_emoji(c) {
+        return Some("emoji".to_string());
+    }
+    match c.script() {
+        Script::Common | Script::Inherited | Script::Unknown => None,
+        Script::Hiragana | Script::Katakana => Some("japanese".to_string()),
+        Script::Hangul => Some("korean".to_string()),
+        script => Some(script.full_name().to_ascii_lowercase()),
+    }
+}
+
+pub(crate) fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0x2300..=0x23FF | 0xFE0F
+    )
+}
+
+/// Scripts a fallback key applies to, for cosmic-text's per-script lookup.
+fn scripts_for_key(key: &str) -> Vec<Script> {
+    match key {
+        "japanese" => vec![Script::Hiragana, Script::Katakana],
+        "korean" => vec![Script::Hangul],
+        _ => {
+            let full_name = key
+                .split('_')
+                .map(|part| {
+                    let mut chars = part.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<_>>()
+                .join("_");
+            Script::from_full_name(&full_name).into_iter().collect()
+        }
+    }
+}
+
+/// Families tried, in order, for a character before falling back to any
+/// installed face: its script's chain, then the emoji and symbol chains.
+fn fallback_families(fallbacks: &FontFallbacks, c: char) -> Vec<&str> {
+    let key = script_key(c);
+    key.iter()
+        .map(String::as_str)
+        .chain(["emoji", "symbols"])
+        .filter_map(|key| fallbacks.scripts.get(key))
+        .flatten()
+        .map(String::as_str)
+        .collect()
+}
+
+/// cosmic-text fallback lists built from the theme's chains, with the
+/// platform defaults appended. The lists live for the rest of the process,
+/// which is fine since a font system is only rebuilt when the theme changes.
+struct ScriptFallback {
+    common: &'static [&'static str],
+    scripts: HashMap<Script, &'static [&'static str]>,
+    /// Configured chains joined with the platform list, per script and
+    /// locale (the platform's Han list depends on the locale).
+    chains: Mutex<HashMap<(Script, String), &'static [&'static str]>>,
+}
+
+fn leak(names: Vec<&str>) -> &'static [&'static str] {
+    let names: Vec<&'static str> = names
+        .into_iter()
+        .map(|name| &*Box::leak(name.to_string().into_boxed_str()))
+        .collect();
+    Box::leak(names.into_boxed_slice())
+}
+
+impl ScriptFallback {
+    fn new(fallbacks: &FontFallbacks) -> Self {
+        let platform = PlatformFallback;
+        let common = ["emoji", "symbols"]
+            .iter()
+            .filter_map(|key| fallbacks.scripts.get(*key))
+            .flatten()
+            .map(String::as_str)
+            .chain(platform.common_fallback().iter().copied())
+            .collect();
+
+        let mut scripts: HashMap<Script, Vec<&str>> = HashMap::new();
+        for (key, families) in &fallbacks.scripts {
+            for script in scripts_for_key(key) {
+                scripts
+                    .entry(script)
+                    .or_default()
+                    .extend(families.iter().map(String::as_str));
+            }
+        }
+
+        Self {
+            common: leak(common),
+            scripts: scripts
+                .into_iter()
+                .map(|(script, families)| (script, leak(families)))
+                .collect(),
+            chains: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Fallback for ScriptFallback {
+    fn common_fallback(&self) -> &[&'static str] {
+        self.common
+    }
+
+    fn forbidden_fallback(&self) -> &[&'static str] {
+        PlatformFallback.forbidden_fallback()
+    }
+
+    fn script_fallback(&self, script: Script, locale: &str) -> &[&'static str] {
+        // Configured families come first; the platform list (with its
+        // locale-specific Han unification) still applies after them.
+        let Some(families) = self.scripts.get(&script) else {
+            return PlatformFallback.script_fallback(script, locale);
+        };
+        let mut chains = self.chains.lock();
+        let key = (script, locale.to_string());
+        if let Some(chain) = chains.get(&key).copied() {
+            return chain;
+        }
+        let platform = PlatformFallback.script_fallback(script, locale);
+        let chain = leak(families.iter().chain(platform).copied().collect());
+        chains.insert(key, chain);
+        chain
+    }
+}
+
+/// Fallback face selection for usvg (PNG and PDF export), mirroring the
+/// order cosmic-text uses during measurement: the character's script chain,
+/// then emoji and symbol chains, then any installed face with the glyph.
+pub fn select_fallback(
+    fallbacks: &FontFallbacks,
+    c: char,
+    exclude: &[ID],
+    db: &mut Arc<Database>,
+) -> Option<ID> {
+    let db: &Database = db;
+    let has_char = |db: &Database, id: ID| {
+        db.with_face_data(id, |data, index| {
+            ttf_parser::Face::parse(data, index)
+                .ok()
+                .and_then(|face| face.glyph_index(c))
+                .is_some()
+        })
+        .unwrap_or(false)
+    };
+
+    let base = exclude.first().and_then(|id| db.face(*id));
+    let (weight, style) = base
+        .map(|face| (face.weight, face.style))
+        .unwrap_or_default();
+
+    for family in fallback_families(fallbacks, c) {
+        let query = cosmic_text::fontdb::Query {
+            families: &[cosmic_text::fontdb::Family::Name(family)],
+            weight,
+            style,
+            ..Default::default()
+        };
+        if let Some(id) = db.query(&query)
+            && !exclude.contains(&id)
+            && has_char(db, id)
+        {
+            return Some(id);
+        }
+    }
+
+    db.faces()
+        .filter(|face| !exclude.contains(&face.id))
+        .find(|face| face.weight == weight && face.style == style && has_char(db, face.id))
+        .or_else(|| {
+            db.faces()
+                .find(|face| !exclude.contains(&face.id) && has_char(db, face.id))
+        })
+        .map(|face| face.id)
 }
 
 impl TextMeasure for CosmicTextMeasure {
@@ -171,7 +901,23 @@ impl TextMeasure for CosmicTextMeasure {
         is_italic: bool,
         max_width: Option<f32>,
     ) -> (f32, f32) {
-        GLOBAL_FONT_SYSTEM.measure_text(text, font_size, is_code, is_bold, is_italic, max_width)
+        let font = FontDescriptor::from_flags(is_code, is_bold, is_italic);
+        let metrics = GLOBAL_FONT_SYSTEM.measure(text, &font, font_size, max_width);
+        (metrics.advance, metrics.height)
+    }
+
+    fn measure(
+        &mut self,
+        text: &str,
+        font: &FontDescriptor,
+        font_size: f32,
+        max_width: Option<f32>,
+    ) -> TextMetrics {
+        GLOBAL_FONT_SYSTEM.measure(text, font, font_size, max_width)
+    }
+
+    fn font_metrics(&mut self, font: &FontDescriptor, font_size: f32) -> FontMetrics {
+        GLOBAL_FONT_SYSTEM.font_metrics(font, font_size)
     }
 }
 
@@ -180,3 +926,111 @@ impl Default for CosmicTextMeasure {
         Self::new().expect("Failed to initialize font system")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Implements only the v1 `measure_text` method.
+    struct FlagsMeasure(Vec<(bool, bool, bool)>);
+    impl TextMeasure for FlagsMeasure {
+        fn measure_text(
+            &mut self,
+            text: &str,
+            font_size: f32,
+            is_code: bool,
+            is_bold: bool,
+            is_italic: bool,
+            _max_width: Option<f32>,
+        ) -> (f32, f32) {
+            self.0.push((is_code, is_bold, is_italic));
+            (text.len() as f32 * font_size * 0.5, font_size)
+        }
+    }
+
+    #[test]
+    fn descriptor_api_adapts_v1_measures() {
+        let mut measure = FlagsMeasure(Vec::new());
+        let font = FontDescriptor {
+            family: FontFamily::Monospace,
+            weight: 700,
+            style: FontStyle::Italic,
+            stretch: FontStretch::Normal,
+        };
+        let metrics = measure.measure("abcd", &font, 10.0, None);
+        assert_eq!(measure.0, vec![(true, true, true)]);
+        assert_eq!(metrics.advance, 20.0);
+        assert_eq!(metrics.line_widths, vec![20.0]);
+        assert_eq!(metrics.ascent, 8.0);
+        assert_eq!(metrics.descent, 2.0);
+
+        let regular = FontDescriptor::from_flags(false, false, false);
+        assert_eq!(regular.family, FontFamily::SansSerif);
+        assert_eq!(regular.weight, 400);
+    }
+
+    #[test]
+    fn script_keys_group_cjk_and_emoji() {
+        assert_eq!(script_key('漢').as_deref(), Some("han"));
+        assert_eq!(script_key('か').as_deref(), Some("japanese"));
+        assert_eq!(script_key('한').as_deref(), Some("korean"));
+        assert_eq!(script_key('ع').as_deref(), Some("arabic"));
+        assert_eq!(script_key('🚀').as_deref(), Some("emoji"));
+        assert_eq!(script_key('a').as_deref(), Some("latin"));
+        assert_eq!(script_key('1'), None);
+    }
+
+    #[test]
+    fn script_keys_map_back_to_scripts() {
+        assert_eq!(scripts_for_key("han"), vec![Script::Han]);
+        assert_eq!(scripts_for_key("old_italic"), vec![Script::Old_Italic]);
+        assert_eq!(
+            scripts_for_key("japanese"),
+            vec![Script::Hiragana, Script::Katakana]
+        );
+        assert!(scripts_for_key("emoji").is_empty());
+    }
+
+    #[test]
+    fn fallback_families_try_script_chain_before_emoji_and_symbols() {
+        let mut fallbacks = FontFallbacks::default();
+        fallbacks
+            .scripts
+            .insert("han".to_string(), vec!["Custom Han".to_string()]);
+
+        let families = fallback_families(&fallbacks, '漢');
+        assert_eq!(families[0], "Custom Han");
+        let emoji_pos = families.iter().position(|f| *f == "Noto Color Emoji");
+        let symbols_pos = families.iter().position(|f| *f == "Noto Sans Symbols");
+        assert!(emoji_pos < symbols_pos);
+
+        let families = fallback_families(&fallbacks, '🚀');
+        assert_eq!(families[0], "Noto Color Emoji");
+    }
+
+    #[test]
+    fn configured_script_chains_keep_the_platform_list() {
+        let mut fallbacks = FontFallbacks::default();
+        fallbacks
+            .scripts
+            .insert("han".to_string(), vec!["Custom Han".to_string()]);
+        let fallback = ScriptFallback::new(&fallbacks);
+
+        for locale in ["zh-CN", "ja"] {
+            let chain = fallback.script_fallback(Script::Han, locale);
+            assert_eq!(chain[0], "Custom Han");
+            assert_eq!(
+                &chain[1..],
+                PlatformFallback.script_fallback(Script::Han, locale)
+            );
+        }
+    }
+
+    #[test]
+    fn has_glyphs_shapes_in_the_given_family() {
+        // The bundled `fonts/` directory provides Fira Sans.
+        assert!(has_glyphs("Fira", "Fira Sans"));
+        assert!(has_glyphs("Fira", "sans-serif"));
+        assert!(!has_glyphs("\u{10fffd}", "Fira Sans"));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::theme::PageSlots;
+use pulldown_cmark::MetadataBlockKind;
+use serde::{Deserialize, Deserializer};
+
+/// Base direction for paragraphs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextDirection {
+    /// Detect per paragraph from its first strong character.
+    #[default]
+    Auto,
+    Ltr,
+    Rtl,
+}
+
+/// Document settings read from a leading YAML (`---`) or TOML (`+++`)
+/// metadata block. Unknown keys are ignored so ordinary front matter
+/// (title, tags, ...) keeps working.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FrontMatter {
+    pub dir: TextDirection,
+    /// Document language (BCP 47 tag such as `en` or `de-DE`), used to
+    /// pick hyphenation patterns.
+    pub lang: Option<String>,
+    /// Number block images as "Figure N"; overrides the theme setting.
+    pub figure_numbers: Option<bool>,
+    /// Prefix headings with section numbers; overrides the theme setting.
+    pub number_headings: Option<bool>,
+    /// Level of the headings numbered `1`, `2`, ...
+    pub heading_number_start: Option<u8>,
+    /// Heading number template, with `{n}` for the section number.
+    pub heading_number_format: Option<String>,
+    /// Leave out the body of `<details>` sections; overrides the theme
+    /// setting.
+    pub collapse_details: Option<bool>,
+    /// Document title, shown by `{title}` in page headers and footers.
+    pub title: Option<String>,
+    /// Document date, shown by `{date}`; today's date when unset.
+    #[serde(deserialize_with = "deserialize_date")]
+    pub date: Option<String>,
+    /// Page header and footer templates; override the theme's.
+    pub page_header: Option<PageSlots>,
+    pub page_footer: Option<PageSlots>,
+    /// Column layout; override the theme settings.
+    pub columns: Option<u8>,
+    pub column_gutter: Option<f32>,
+    pub column_span: Option<bool>,
+}
+
+/// Accept dates as strings or as TOML's native dates (`date = 2024-05-01`).
+pub(crate) fn deserialize_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<toml::Value>::deserialize(deserializer)? {
+        Some(toml::Value::String(date)) => Some(date),
+        Some(value) => Some(value.to_string()),
+        None => None,
+    })
+}
+
+impl FrontMatter {
+    pub fn parse(kind: MetadataBlockKind, source: &str) -> Result<Self, String> {
+        if source.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        match kind {
+            MetadataBlockKind::YamlStyle => serde_yaml::from_str(source)
+                .map_err(|e| format!("Failed to parse YAML front matter: {}", e)),
+            MetadataBlockKind::PlusesStyle => toml::from_str(source)
+                .map_err(|e| format!("Failed to parse TOML front matter: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dir_from_yaml_and_toml() {
+        let yaml = FrontMatter::parse(MetadataBlockKind::YamlStyle, "title: Test\ndir: rtl\n");
+        assert_eq!(yaml.unwrap().dir, TextDirection::Rtl);
+
+        let toml = FrontMatter::parse(
+            MetadataBlockKind::PlusesStyle,
+            "dir = \"ltr\"\nlang = \"de-DE\"\nfigure_numbers = true\nnumber_headings = true\n",
+        )
+        .unwrap();
+        assert_eq!(toml.dir, TextDirection::Ltr);
+        assert_eq!(toml.lang.as_deref(), Some("de-DE"));
+        assert_eq!(toml.figure_numbers, Some(true));
+        assert_eq!(toml.number_headings, Some(true));
+    }
+
+    #[test]
+    fn parses_page_templates_and_dates() {
+        let yaml = FrontMatter::parse(
+            MetadataBlockKind::YamlStyle,
+            "title: Guide\ndate: 2024-05-01\npage_footer:\n  center: \"{page}\"\n",
+        )
+        .unwrap();
+        assert_eq!(yaml.title.as_deref(), Some("Guide"));
+        assert_eq!(yaml.date.as_deref(), Some("2024-05-01"));
+        let footer = yaml.page_footer.unwrap();
+        assert_eq!(footer.center, "{page}");
+        assert!(footer.left.is_empty());
+
+        let toml =
+            FrontMatter::parse(MetadataBlockKind::PlusesStyle, "date = 2024-05-01\n").unwrap();
+        assert_eq!(toml.date.as_deref(), Some("2024-05-01"));
+    }
+
+    #[test]
+    fn missing_dir_defaults_to_auto() {
+        let fm = FrontMatter::parse(MetadataBlockKind::YamlStyle, "title: Test\n").unwrap();
+        assert_eq!(fm.dir, TextDirection::Auto);
+    }
+
+    #[test]
+    fn invalid_dir_is_an_error() {
+        assert!(FrontMatter::parse(MetadataBlockKind::YamlStyle, "dir: sideways\n").is_err());
+    }
+}
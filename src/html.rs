@@ -0,0 +1,304 @@
+//! Minimal HTML tokenizer for the block-level HTML subset found in READMEs.
+//!
+//! This is not a conforming HTML parser: it splits source into tags, text
+//! and comments so the renderer can map a small set of known elements onto
+//! its own primitives, and reports `None` for anything it cannot tokenize so
+//! callers can fall back to showing the source.
+
+/// A piece of HTML source.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HtmlToken {
+    /// Text between tags, with character references decoded.
+    Text(String),
+    /// An opening or self-closing tag. `raw` is the tag's source text.
+    Start {
+        name: String,
+        attrs: Vec<(String, String)>,
+        self_closing: bool,
+        raw: String,
+    },
+    /// A closing tag.
+    End { name: String, raw: String },
+    /// A `<!-- ... -->` comment.
+    Comment,
+}
+
+impl HtmlToken {
+    /// Value of attribute `name` on a start tag (names are matched
+    /// case-insensitively; they are stored lowercase).
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        match self {
+            HtmlToken::Start { attrs, .. } => attrs
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// Elements that never have content or a closing tag.
+pub fn is_void_element(name: &str) -> bool {
+    matches!(
+        name,
+        "br" | "img" | "hr" | "wbr" | "input" | "meta" | "link" | "source"
+    )
+}
+
+/// Split `source` into tokens. Returns `None` for markup this tokenizer does
+/// not understand (doctypes, processing instructions, unterminated tags).
+pub fn tokenize(source: &str) -> Option<Vec<HtmlToken>> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            tokens.push(HtmlToken::Text(decode_entities(rest)));
+            break;
+        };
+        if lt > 0 {
+            tokens.push(HtmlToken::Text(decode_entities(&rest[..lt])));
+            rest = &rest[lt..];
+        }
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->")?;
+            tokens.push(HtmlToken::Comment);
+            rest = &comment[end + 3..];
+            continue;
+        }
+
+        let next = rest[1..].chars().next();
+        let is_tag = match next {
+            Some('/') => rest[2..].starts_with(|c: char| c.is_ascii_alphabetic()),
+            Some(c) => c.is_ascii_alphabetic(),
+            None => false,
+        };
+        if !is_tag {
+            if next == Some('!') || next == Some('?') {
+                return None;
+            }
+            // A lone `<` in text, as in "a < b".
+            tokens.push(HtmlToken::Text("<".to_string()));
+            rest = &rest[1..];
+            continue;
+        }
+
+        let end = tag_end(rest)?;
+        let raw = &rest[..=end];
+        rest = &rest[end + 1..];
+        tokens.push(parse_tag(raw)?);
+    }
+
+    Some(tokens)
+}
+
+/// Byte index of the `>` closing the tag at the start of `source`, skipping
+/// over quoted attribute values.
+fn tag_end(source: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in source.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(i),
+            (None, '<') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_tag(raw: &str) -> Option<HtmlToken> {
+    let inner = &raw[1..raw.len() - 1];
+    if let Some(name) = inner.strip_prefix('/') {
+        let name = name.trim().to_ascii_lowercase();
+        if !is_tag_name(&name) {
+            return None;
+        }
+        return Some(HtmlToken::End {
+            name,
+            raw: raw.to_string(),
+        });
+    }
+
+    let self_closing = inner.trim_end().ends_with('/');
+    let inner = inner.trim_end().trim_end_matches('/');
+    let name_end = inner
+        .find(|c: char| c.is_whitespace())
+        .unwrap_or(inner.len());
+    let name = inner[..name_end].to_ascii_lowercase();
+    if !is_tag_name(&name) {
+        return None;
+    }
+
+    Some(HtmlToken::Start {
+        attrs: parse_attrs(&inner[name_end..]),
+        name,
+        self_closing,
+        raw: raw.to_string(),
+    })
+}
+
+fn is_tag_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Parse `name="value" name='value' name=value name` attribute lists.
+fn parse_attrs(source: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut rest = source.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        let mut value = String::new();
+        if let Some(after_eq) = rest.strip_prefix('=') {
+            let after_eq = after_eq.trim_start();
+            let (raw_value, remainder) = match after_eq.chars().next() {
+                Some(q @ ('"' | '\'')) => {
+                    let body = &after_eq[1..];
+                    let close = body.find(q).unwrap_or(body.len());
+                    (&body[..close], body.get(close + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let end = after_eq.find(char::is_whitespace).unwrap_or(after_eq.len());
+                    (&after_eq[..end], &after_eq[end..])
+                }
+            };
+            value = decode_entities(raw_value);
+            rest = remainder.trim_start();
+        }
+
+        if !name.is_empty() {
+            attrs.push((name, value));
+        } else if name_end == 0 {
+            // Stray character (e.g. a lone quote); skip it.
+            let mut chars = rest.chars();
+            chars.next();
+            rest = chars.as_str().trim_start();
+        }
+    }
+    attrs
+}
+
+/// Decode named and numeric character references. Unknown references are
+/// kept as written.
+pub fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| {
+                let name = &rest[1..=end];
+                let c = if let Some(num) = name.strip_prefix('#') {
+                    let code = match num.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => num.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                } else {
+                    named_entity(name)?
+                };
+                Some((c, end + 2))
+            });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{A0}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "mdash" => '—',
+        "ndash" => '–',
+        "hellip" => '…',
+        "middot" => '·',
+        "bull" => '•',
+        "laquo" => '«',
+        "raquo" => '»',
+        "larr" => '←',
+        "rarr" => '→',
+        "uarr" => '↑',
+        "darr" => '↓',
+        "times" => '×',
+        "check" => '✓',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_tags_attributes_and_text() {
+        let tokens = tokenize(
+            r#"<p align="center"><img src='a.png' width=200 alt="A &amp; B"/> x &lt; y</p>"#,
+        )
+        .unwrap();
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].attr("align"), Some("center"));
+        assert!(
+            matches!(&tokens[1], HtmlToken::Start { name, self_closing: true, .. } if name == "img")
+        );
+        assert_eq!(tokens[1].attr("width"), Some("200"));
+        assert_eq!(tokens[1].attr("alt"), Some("A & B"));
+        assert_eq!(tokens[2], HtmlToken::Text(" x < y".to_string()));
+        assert!(matches!(&tokens[3], HtmlToken::End { name, .. } if name == "p"));
+    }
+
+    #[test]
+    fn quoted_angle_brackets_and_comments() {
+        let tokens = tokenize(r#"<!-- note --><a title="a > b" href=x>go</a>"#).unwrap();
+        assert_eq!(tokens[0], HtmlToken::Comment);
+        assert_eq!(tokens[1].attr("title"), Some("a > b"));
+        assert_eq!(tokens[2], HtmlToken::Text("go".to_string()));
+    }
+
+    #[test]
+    fn unsupported_markup_is_rejected() {
+        assert!(tokenize("<!DOCTYPE html>").is_none());
+        assert!(tokenize("<p unterminated").is_none());
+        assert_eq!(
+            tokenize("1 < 2").unwrap(),
+            vec![
+                HtmlToken::Text("1 ".to_string()),
+                HtmlToken::Text("<".to_string()),
+                HtmlToken::Text(" 2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_numeric_and_unknown_entities() {
+        assert_eq!(decode_entities("&#65;&#x42;&copy;&bogus;&"), "AB©&bogus;&");
+    }
+}
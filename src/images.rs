@@ -0,0 +1,388 @@
+//! Image format detection and normalization for embedding in SVG output.
+//!
+//! Formats are identified by their magic bytes rather than by file
+//! extension or server-declared MIME type. PNG, JPEG and SVG are embedded
+//! as-is; GIF (first frame), WebP and BMP are decoded and re-encoded as PNG
+//! so every consumer of the SVG (resvg, svg2pdf, browsers) can draw them.
+//! Rasters can optionally be resampled to the size they are drawn at and
+//! re-encoded to keep the output small.
+
+use base64::Engine;
+use resvg::usvg;
+use std::io::Cursor;
+
+/// How raster images are embedded in the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ImageOptions {
+    /// Resample rasters with more than this many pixels per drawn SVG
+    /// unit (e.g. the PNG scale, or DPI / 96). `None` embeds the original
+    /// pixels.
+    pub max_density: Option<f32>,
+    /// Re-encode opaque rasters as JPEG at this quality (1-100) when that
+    /// is smaller than the original.
+    pub jpeg_quality: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    WebP,
+    Bmp,
+    Avif,
+    Svg,
+}
+
+impl ImageFormat {
+    /// Identify the format from the file's leading bytes.
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            return Some(ImageFormat::Png);
+        }
+        if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            return Some(ImageFormat::Jpeg);
+        }
+        if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            return Some(ImageFormat::Gif);
+        }
+        if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            return Some(ImageFormat::WebP);
+        }
+        if bytes.starts_with(b"BM") && bytes.len() >= 26 {
+            return Some(ImageFormat::Bmp);
+        }
+        if bytes.len() >= 12
+            && &bytes[4..8] == b"ftyp"
+            && matches!(&bytes[8..12], b"avif" | b"avis")
+        {
+            return Some(ImageFormat::Avif);
+        }
+        if looks_like_svg(bytes) {
+            return Some(ImageFormat::Svg);
+        }
+        None
+    }
+
+    /// Format named by a MIME type such as `image/png`.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let mime = mime.split(';').next().unwrap_or(mime).trim();
+        Some(match mime.to_ascii_lowercase().as_str() {
+            "image/png" => ImageFormat::Png,
+            "image/jpeg" | "image/jpg" => ImageFormat::Jpeg,
+            "image/gif" => ImageFormat::Gif,
+            "image/webp" => ImageFormat::WebP,
+            "image/bmp" | "image/x-bmp" | "image/x-ms-bmp" => ImageFormat::Bmp,
+            "image/avif" => ImageFormat::Avif,
+            "image/svg+xml" => ImageFormat::Svg,
+            _ => return None,
+        })
+    }
+
+    /// Format suggested by a file extension (without the dot).
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Some(match extension.to_ascii_lowercase().as_str() {
+            "png" => ImageFormat::Png,
+            "jpg" | "jpeg" => ImageFormat::Jpeg,
+            "gif" => ImageFormat::Gif,
+            "webp" => ImageFormat::WebP,
+            "bmp" => ImageFormat::Bmp,
+            "avif" => ImageFormat::Avif,
+            "svg" => ImageFormat::Svg,
+            _ => return None,
+        })
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::WebP => "image/webp",
+            ImageFormat::Bmp => "image/bmp",
+            ImageFormat::Avif => "image/avif",
+            ImageFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+/// Image bytes ready to embed as a data URL, with their intrinsic size.
+#[derive(Debug)]
+pub struct EmbeddableImage {
+    pub mime: &'static str,
+    pub bytes: Vec<u8>,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl EmbeddableImage {
+    pub fn data_url(&self) -> String {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&self.bytes);
+        format!("data:{};base64,{}", self.mime, encoded)
+    }
+
+    /// Resample and/or re-encode the image for drawing at `width`×`height`
+    /// SVG units. Returns `None` when the original bytes should be kept:
+    /// for SVG, when no option applies, or when the result is not smaller.
+    pub fn optimized(
+        &self,
+        width: f32,
+        height: f32,
+        options: &ImageOptions,
+    ) -> Result<Option<EmbeddableImage>, String> {
+        if self.mime == ImageFormat::Svg.mime() || self.width <= 0.0 || self.height <= 0.0 {
+            return Ok(None);
+        }
+        // Keep the aspect ratio and at least the target density on both axes.
+        let scale = options
+            .max_density
+            .map(|density| (width * density / self.width).max(height * density / self.height))
+            .filter(|&scale| scale < 1.0);
+        if scale.is_none() && options.jpeg_quality.is_none() {
+            return Ok(None);
+        }
+
+        let mut decoded = image::load_from_memory(&self.bytes)
+            .map_err(|e| format!("Failed to decode image for resampling: {}", e))?;
+        if let Some(scale) = scale {
+            let target_width = (self.width * scale).round().max(1.0) as u32;
+            let target_height = (self.height * scale).round().max(1.0) as u32;
+            decoded = decoded.resize_exact(
+                target_width,
+                target_height,
+                image::imageops::FilterType::Lanczos3,
+            );
+        }
+
+        let opaque =
+            !decoded.color().has_alpha() || decoded.to_rgba8().pixels().all(|p| p[3] == 255);
+        let mut bytes = Vec::new();
+        let mime = match options.jpeg_quality {
+            Some(quality) if opaque => {
+                let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(
+                    Cursor::new(&mut bytes),
+                    quality.clamp(1, 100),
+                );
+                decoded
+                    .to_rgb8()
+                    .write_with_encoder(encoder)
+                    .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+                ImageFormat::Jpeg.mime()
+            }
+            _ => {
+                decoded
+                    .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+                    .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+                ImageFormat::Png.mime()
+            }
+        };
+
+        if bytes.len() >= self.bytes.len() {
+            return Ok(None);
+        }
+        Ok(Some(EmbeddableImage {
+            mime,
+            bytes,
+            width: decoded.width() as f32,
+            height: decoded.height() as f32,
+        }))
+    }
+}
+
+/// Detect the format of `bytes` and convert it to one every SVG consumer can
+/// draw. `declared` (from the extension or Content-Type) is only used when
+/// the bytes are not recognized.
+pub fn prepare(bytes: Vec<u8>, declared: Option<ImageFormat>) -> Result<EmbeddableImage, String> {
+    let format = ImageFormat::sniff(&bytes)
+        .or(declared)
+        .ok_or_else(|| "unrecognized image format".to_string())?;
+
+    match format {
+        ImageFormat::Svg => {
+            let tree = usvg::Tree::from_data(&bytes, &usvg::Options::default())
+                .map_err(|e| format!("Failed to read SVG size: {}", e))?;
+            let size = tree.size();
+            Ok(EmbeddableImage {
+                mime: format.mime(),
+                bytes,
+                width: size.width(),
+                height: size.height(),
+            })
+        }
+        ImageFormat::Png | ImageFormat::Jpeg => {
+            let size = imagesize::blob_size(&bytes)
+                .map_err(|e| format!("Failed to read image size: {}", e))?;
+            Ok(EmbeddableImage {
+                mime: format.mime(),
+                bytes,
+                width: size.width as f32,
+                height: size.height as f32,
+            })
+        }
+        ImageFormat::Gif | ImageFormat::WebP | ImageFormat::Bmp => transcode_to_png(&bytes, format),
+        ImageFormat::Avif => Err("AVIF images are not supported (no AVIF decoder)".to_string()),
+    }
+}
+
+/// Decode `bytes` (the first frame, for animations) and re-encode as PNG.
+fn transcode_to_png(bytes: &[u8], format: ImageFormat) -> Result<EmbeddableImage, String> {
+    let decoder_format = match format {
+        ImageFormat::Gif => image::ImageFormat::Gif,
+        ImageFormat::WebP => image::ImageFormat::WebP,
+        _ => image::ImageFormat::Bmp,
+    };
+    let decoded = image::load_from_memory_with_format(bytes, decoder_format)
+        .map_err(|e| format!("Failed to decode {} image: {}", format.mime(), e))?;
+
+    let mut png = Vec::new();
+    decoded
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to convert {} image to PNG: {}", format.mime(), e))?;
+
+    Ok(EmbeddableImage {
+        mime: ImageFormat::Png.mime(),
+        bytes: png,
+        width: decoded.width() as f32,
+        height: decoded.height() as f32,
+    })
+}
+
+fn looks_like_svg(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(1024)];
+    let Ok(text) = std::str::from_utf8(head).or_else(|e| {
+        // The 1 KiB window may end inside a multi-byte character.
+        std::str::from_utf8(&head[..e.valid_up_to()])
+    }) else {
+        return false;
+    };
+    let text = text.trim_start_matches('\u{FEFF}').trim_start();
+    (text.starts_with("<svg") || text.starts_with("<?xml") || text.starts_with("<!--"))
+        && text.contains("<svg")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(format: image::ImageFormat) -> Vec<u8> {
+        let image = image::RgbaImage::from_pixel(3, 2, image::Rgba([200, 40, 40, 255]));
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn sniffs_formats_by_magic_bytes() {
+        assert_eq!(
+            ImageFormat::sniff(&encode(image::ImageFormat::Png)),
+            Some(ImageFormat::Png)
+        );
+        assert_eq!(
+            ImageFormat::sniff(&encode(image::ImageFormat::Gif)),
+            Some(ImageFormat::Gif)
+        );
+        assert_eq!(
+            ImageFormat::sniff(&encode(image::ImageFormat::WebP)),
+            Some(ImageFormat::WebP)
+        );
+        assert_eq!(
+            ImageFormat::sniff(&encode(image::ImageFormat::Bmp)),
+            Some(ImageFormat::Bmp)
+        );
+        assert_eq!(
+            ImageFormat::sniff(b"\0\0\0\x1cftypavif\0\0\0\0"),
+            Some(ImageFormat::Avif)
+        );
+        assert_eq!(
+            ImageFormat::sniff(b"\xEF\xBB\xBF<?xml version=\"1.0\"?>\n<svg xmlns=\"x\"/>"),
+            Some(ImageFormat::Svg)
+        );
+        assert_eq!(
+            ImageFormat::sniff(b"  <svg xmlns=\"http://www.w3.org/2000/svg\"/>"),
+            Some(ImageFormat::Svg)
+        );
+        assert_eq!(ImageFormat::sniff(b"hello"), None);
+    }
+
+    #[test]
+    fn transcodes_gif_webp_and_bmp_to_png() {
+        for format in [
+            image::ImageFormat::Gif,
+            image::ImageFormat::WebP,
+            image::ImageFormat::Bmp,
+        ] {
+            // A misleading declared type does not matter: the bytes win.
+            let prepared = prepare(encode(format), Some(ImageFormat::Jpeg)).unwrap();
+            assert_eq!(prepared.mime, "image/png", "{format:?}");
+            assert_eq!((prepared.width, prepared.height), (3.0, 2.0));
+            assert_eq!(ImageFormat::sniff(&prepared.bytes), Some(ImageFormat::Png));
+        }
+    }
+
+    fn noisy_png(width: u32, height: u32, alpha: u8) -> EmbeddableImage {
+        let image = image::RgbaImage::from_fn(width, height, |x, y| {
+            let v = ((x * 7919 + y * 104_729) % 251) as u8;
+            image::Rgba([v, v.wrapping_mul(3), v.wrapping_mul(7), alpha])
+        });
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        prepare(bytes, None).unwrap()
+    }
+
+    #[test]
+    fn optimized_resamples_to_drawn_size_and_recompresses() {
+        let original = noisy_png(400, 200, 255);
+
+        // Drawn at 100×50 with a 2× density budget: 200×100 pixels.
+        let options = ImageOptions {
+            max_density: Some(2.0),
+            jpeg_quality: None,
+        };
+        let small = original.optimized(100.0, 50.0, &options).unwrap().unwrap();
+        assert_eq!((small.width, small.height), (200.0, 100.0));
+        assert_eq!(small.mime, "image/png");
+        assert!(small.bytes.len() < original.bytes.len());
+
+        // Already at or below the budget: keep the original bytes.
+        assert!(
+            original
+                .optimized(400.0, 200.0, &options)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            original
+                .optimized(100.0, 50.0, &ImageOptions::default())
+                .unwrap()
+                .is_none()
+        );
+
+        let jpeg = ImageOptions {
+            max_density: None,
+            jpeg_quality: Some(70),
+        };
+        let recompressed = original.optimized(400.0, 200.0, &jpeg).unwrap().unwrap();
+        assert_eq!(recompressed.mime, "image/jpeg");
+        assert_eq!((recompressed.width, recompressed.height), (400.0, 200.0));
+
+        // Transparency rules out JPEG; re-encoding the same PNG is not smaller.
+        let translucent = noisy_png(40, 20, 128);
+        assert!(translucent.optimized(40.0, 20.0, &jpeg).unwrap().is_none());
+    }
+
+    #[test]
+    fn png_passes_through_and_undecodable_data_errors() {
+        let png = encode(image::ImageFormat::Png);
+        let prepared = prepare(png.clone(), None).unwrap();
+        assert_eq!(prepared.bytes, png);
+
+        assert!(prepare(b"not an image".to_vec(), None).is_err());
+        assert!(prepare(b"GIF89a truncated".to_vec(), None).is_err());
+        let avif = prepare(b"\0\0\0\x1cftypavif\0\0\0\0".to_vec(), None).unwrap_err();
+        assert!(avif.contains("AVIF"));
+    }
+}
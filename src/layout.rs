@@ -6,7 +6,7 @@
 //! - `TextLayout`: Trait for text layout engines
 //! - Collision detection utilities
 
-use crate::fonts::TextMeasure;
+use crate::fonts::{FontDescriptor, TextMeasure, TextMetrics};
 
 /// Represents a rectangular bounding box.
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -92,6 +92,23 @@ impl GlyphBox {
         }
     }
 
+    /// Create a GlyphBox from measured text metrics, with the baseline at `y`.
+    pub fn from_metrics(x: f32, y: f32, metrics: &TextMetrics) -> Self {
+        Self {
+            x,
+            y,
+            advance_width: metrics.advance,
+            ink_bounds: Rect::new(
+                x,
+                y - metrics.ascent,
+                metrics.advance,
+                metrics.ascent + metrics.descent,
+            ),
+            ascent: metrics.ascent,
+            descent: metrics.descent,
+        }
+    }
+
     /// Get the right edge of the advance width.
     pub fn right(&self) -> f32 {
         self.x + self.advance_width
@@ -167,11 +184,10 @@ impl<T: TextMeasure> TextLayout for TextLayoutEngine<T> {
         is_bold: bool,
         is_italic: bool,
     ) -> Vec<GlyphBox> {
-        let (width, _height) = self
-            .measure
-            .measure_text(text, font_size, is_code, is_bold, is_italic, None);
+        let font = FontDescriptor::from_flags(is_code, is_bold, is_italic);
+        let metrics = self.measure.measure(text, &font, font_size, None);
 
-        vec![GlyphBox::new_estimated(0.0, 0.0, width, font_size)]
+        vec![GlyphBox::from_metrics(0.0, 0.0, &metrics)]
     }
 
     fn layout_line(
@@ -458,6 +474,23 @@ mod tests {
         assert_eq!(padded.h, 40.0);
     }
 
+    #[test]
+    fn test_glyph_box_from_metrics() {
+        let metrics = TextMetrics {
+            advance: 40.0,
+            ascent: 12.0,
+            descent: 4.0,
+            line_gap: 0.0,
+            height: 16.0,
+            line_widths: vec![40.0],
+        };
+        let glyph = GlyphBox::from_metrics(5.0, 20.0, &metrics);
+        assert_eq!(glyph.top(), 8.0);
+        assert_eq!(glyph.bottom(), 24.0);
+        assert_eq!(glyph.right(), 45.0);
+        assert_eq!(glyph.ink_bounds.h, 16.0);
+    }
+
     #[test]
     fn test_glyph_box_new_estimated() {
         let glyph = GlyphBox::new_estimated(0.0, 16.0, 100.0, 16.0);
@@ -1,8 +1,18 @@
+pub mod book;
+pub mod columns;
+pub mod embed;
+pub mod emoji;
 pub mod export;
 pub mod fonts;
+pub mod frontmatter;
+pub mod html;
+pub mod images;
 pub mod layout;
+pub mod links;
 pub mod math;
 pub mod mermaid;
+pub mod pages;
 pub mod renderer;
+pub mod slides;
 pub mod theme;
 pub mod xml;
@@ -0,0 +1,463 @@
+//! Heading anchors and links between documents rendered together.
+//!
+//! Headings get GitHub-style slugs (`## Quick Start` → `#quick-start`).
+//! When a directory is rendered, a [`LinkMap`] records each document's
+//! output file and anchors, so `[setup](./setup.md#install)` and
+//! `[[Setup]]` links point at the rendered files and links to missing
+//! pages or headings can be reported.
+
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+/// GitHub-style anchor for a heading: lowercased, spaces become `-` and
+/// punctuation other than `-` and `_` is dropped.
+pub fn slugify(text: &str) -> String {
+    text.trim()
+        .chars()
+        .flat_map(|c| {
+            let keep = if c.is_alphanumeric() || c == '-' || c == '_' {
+                Some(c)
+            } else if c.is_whitespace() {
+                Some('-')
+            } else {
+                None
+            };
+            keep.into_iter().flat_map(char::to_lowercase)
+        })
+        .collect()
+}
+
+/// Hands out unique heading slugs, numbering repeats like GitHub does
+/// (`notes`, `notes-1`, `notes-2`).
+#[derive(Debug, Default)]
+pub struct Slugger {
+    used: HashSet<String>,
+}
+
+impl Slugger {
+    pub fn slug(&mut self, text: &str) -> String {
+        let base = slugify(text);
+        let mut slug = base.clone();
+        let mut n = 0;
+        while !self.used.insert(slug.clone()) {
+            n += 1;
+            slug = format!("{}-{}", base, n);
+        }
+        slug
+    }
+}
+
+/// A heading's level, plain text and anchor.
+pub(crate) struct HeadingText {
+    pub level: HeadingLevel,
+    pub text: String,
+    pub slug: String,
+}
+
+/// The headings in a stream of parser events, in document order.
+pub(crate) fn headings<'a>(events: impl IntoIterator<Item = &'a Event<'a>>) -> Vec<HeadingText> {
+    let mut slugger = Slugger::default();
+    let mut headings = Vec::new();
+    let mut heading: Option<(HeadingLevel, String)> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => heading = Some((*level, String::new())),
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, text)) = heading.take() {
+                    headings.push(HeadingText {
+                        level,
+                        slug: slugger.slug(&text),
+                        text,
+                    });
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, heading)) = heading.as_mut() {
+                    heading.push_str(text);
+                }
+            }
+            _ => {}
+        }
+    }
+    headings
+}
+
+/// The heading anchors of `markdown`, in document order.
+pub fn anchors(markdown: &str) -> Vec<String> {
+    let events: Vec<Event> =
+        Parser::new_ext(markdown, crate::renderer::markdown_options()).collect();
+    headings(&events)
+        .into_iter()
+        .map(|heading| heading.slug)
+        .collect()
+}
+
+/// The documents rendered together, keyed by source path relative to the
+/// rendered directory.
+#[derive(Debug, Default)]
+pub struct LinkMap {
+    documents: Vec<LinkedDocument>,
+}
+
+#[derive(Debug)]
+struct LinkedDocument {
+    source: PathBuf,
+    output: PathBuf,
+    anchors: HashSet<String>,
+    /// Output files of a document written one file per page, and the page
+    /// each anchor is on; empty when it is written to `output`.
+    pages: Vec<PathBuf>,
+    anchor_pages: HashMap<String, usize>,
+}
+
+impl LinkMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a document. `source` and `output` are relative to the
+    /// rendered directory; `markdown` supplies the heading anchors.
+    pub fn add(&mut self, source: impl AsRef<Path>, output: impl AsRef<Path>, markdown: &str) {
+        self.documents.push(LinkedDocument {
+            source: normalize(source.as_ref()),
+            output: normalize(output.as_ref()),
+            anchors: anchors(markdown).into_iter().collect(),
+            pages: Vec::new(),
+            anchor_pages: HashMap::new(),
+        });
+    }
+
+    /// Record that document `source` is written one file per page. `pages`
+    /// are the files, relative to the rendered directory, and `anchor_pages`
+    /// the page index each anchor is on; links to it then point at the page
+    /// showing their heading, or the first page.
+    pub fn set_pages(
+        &mut self,
+        source: impl AsRef<Path>,
+        pages: Vec<PathBuf>,
+        anchor_pages: HashMap<String, usize>,
+    ) {
+        let source = normalize(source.as_ref());
+        if let Some(doc) = self.documents.iter_mut().find(|doc| doc.source == source) {
+            doc.pages = pages.iter().map(|page| normalize(page)).collect();
+            doc.anchor_pages = anchor_pages;
+        }
+    }
+
+    /// Resolve link `dest` written in document `from`. Wiki-links
+    /// (`[[Page Name#Section]]`) name a page by its file name. Returns the
+    /// href to draw, with a warning when the target page or heading does
+    /// not exist; broken links keep their original destination.
+    pub fn resolve(&self, from: &Path, dest: &str, wiki: bool) -> (String, Option<String>) {
+        let from = normalize(from);
+        if !wiki && is_external(dest) {
+            return (dest.to_string(), None);
+        }
+        let (path, fragment) = dest.split_once('#').unwrap_or((dest, ""));
+
+        let target = if path.is_empty() {
+            self.documents.iter().find(|doc| doc.source == from)
+        } else if wiki {
+            match self.find_page(path) {
+                Some(doc) => Some(doc),
+                None => {
+                    return (
+                        dest.to_string(),
+                        Some(format!("no page named \"{}\"", path)),
+                    );
+                }
+            }
+        } else {
+            let decoded = percent_decode(path);
+            if !is_markdown_path(&decoded) {
+                return (dest.to_string(), None);
+            }
+            let source = normalize(&from.parent().unwrap_or(Path::new("")).join(&decoded));
+            match self.documents.iter().find(|doc| doc.source == source) {
+                Some(doc) => Some(doc),
+                None => {
+                    return (
+                        dest.to_string(),
+                        Some(format!("{} is not one of the rendered documents", path)),
+                    );
+                }
+            }
+        };
+        let Some(target) = target else {
+            return (dest.to_string(), None);
+        };
+
+        let anchor = if fragment.is_empty() {
+            None
+        } else if target.anchors.contains(fragment) {
+            Some(fragment.to_string())
+        } else {
+            let slug = slugify(fragment);
+            if !target.anchors.contains(&slug) {
+                return (
+                    dest.to_string(),
+                    Some(format!(
+                        "{} has no heading \"#{}\"",
+                        target.source.display(),
+                        fragment
+                    )),
+                );
+            }
+            Some(slug)
+        };
+
+        let output = match target.pages.as_slice() {
+            [] => &target.output,
+            pages => {
+                let page = anchor
+                    .as_ref()
+                    .and_then(|anchor| target.anchor_pages.get(anchor))
+                    .copied()
+                    .unwrap_or(0);
+                &pages[page.min(pages.len() - 1)]
+            }
+        };
+        // A link within a paged document may be on another page than its
+        // heading, so it names the page file too.
+        let mut href = if target.source == from && target.pages.is_empty() {
+            String::new()
+        } else {
+            let from_dir = self
+                .documents
+                .iter()
+                .find(|doc| doc.source == from)
+                .and_then(|doc| doc.output.parent())
+                .unwrap_or(Path::new(""));
+            relative_href(from_dir, output)
+        };
+        if let Some(anchor) = anchor {
+            href.push('#');
+            href.push_str(&anchor);
+        }
+        (href, None)
+    }
+
+    /// The document an href drawn by [`resolve`](Self::resolve) in document
+    /// `from` leads to, as its index in the order documents were added, and
+    /// the anchor in it (empty for its top). `None` when the href leaves the
+    /// rendered documents.
+    pub fn locate(&self, from: &Path, href: &str) -> Option<(usize, String)> {
+        if is_external(href) {
+            return None;
+        }
+        let from = normalize(from);
+        let (path, fragment) = href.split_once('#').unwrap_or((href, ""));
+        let index = if path.is_empty() {
+            self.documents.iter().position(|doc| doc.source == from)?
+        } else {
+            let from_dir = self
+                .documents
+                .iter()
+                .find(|doc| doc.source == from)
+                .and_then(|doc| doc.output.parent())
+                .unwrap_or(Path::new(""));
+            let output = normalize(&from_dir.join(percent_decode(path)));
+            self.documents
+                .iter()
+                .position(|doc| doc.output == output || doc.pages.contains(&output))?
+        };
+        Some((index, fragment.to_string()))
+    }
+
+    /// Find a page by name: its path without the extension, or just the
+    /// file name, compared case-insensitively or by slug.
+    fn find_page(&self, name: &str) -> Option<&LinkedDocument> {
+        let name = name.trim();
+        let key = slugify(name);
+        let stems = |doc: &LinkedDocument| {
+            let full = doc.source.with_extension("");
+            let stem = doc
+                .source
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned());
+            [
+                full.to_string_lossy().replace('\\', "/"),
+                stem.unwrap_or_default(),
+            ]
+        };
+        let exact = self.documents.iter().find(|doc| {
+            stems(doc)
+                .iter()
+                .any(|stem| stem.eq_ignore_ascii_case(name))
+        });
+        exact.or_else(|| {
+            self.documents
+                .iter()
+                .find(|doc| stems(doc).iter().any(|stem| slugify(stem) == key))
+        })
+    }
+}
+
+/// Whether a link leaves the rendered documents: URLs with a scheme
+/// (`https:`, `mailto:`) and absolute paths.
+fn is_external(dest: &str) -> bool {
+    dest.starts_with('/') || has_scheme(dest)
+}
+
+/// Whether `dest` is a URL with a scheme, like `https:` or `mailto:`.
+pub(crate) fn has_scheme(dest: &str) -> bool {
+    let Some((scheme, _)) = dest.split_once(':') else {
+        return false;
+    };
+    scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+fn is_markdown_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+}
+
+/// Decode `%XX` escapes (e.g. `my%20page.md`); invalid escapes are kept.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| path.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).unwrap_or_else(|_| path.to_string())
+}
+
+/// Resolve `.` and `..` components without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut parts: Vec<&std::ffi::OsStr> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+    parts.iter().collect()
+}
+
+/// URL path from directory `from_dir` to file `to`, both relative to the
+/// same root.
+fn relative_href(from_dir: &Path, to: &Path) -> String {
+    let from: Vec<_> = from_dir.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(
+        to[common..]
+            .iter()
+            .map(|part| part.as_os_str().to_string_lossy().replace(' ', "%20")),
+    );
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs_follow_github() {
+        assert_eq!(slugify("Quick Start!"), "quick-start");
+        assert_eq!(slugify("C++ & Rust_2"), "c--rust_2");
+        let mut slugger = Slugger::default();
+        assert_eq!(slugger.slug("Notes"), "notes");
+        assert_eq!(slugger.slug("Notes"), "notes-1");
+        assert_eq!(slugger.slug("notes"), "notes-2");
+    }
+
+    #[test]
+    fn resolves_relative_and_wiki_links_to_outputs() {
+        let mut links = LinkMap::new();
+        links.add("index.md", "index.svg", "# Home\n");
+        links.add("guide/setup.md", "guide/setup.svg", "# Setup\n## Install\n");
+        links.add("Page Name.md", "Page Name.svg", "# Page\n");
+
+        let from = Path::new("index.md");
+        assert_eq!(
+            links.resolve(from, "./guide/setup.md#install", false),
+            ("guide/setup.svg#install".to_string(), None)
+        );
+        assert_eq!(
+            links.resolve(Path::new("guide/setup.md"), "../index.md", false),
+            ("../index.svg".to_string(), None)
+        );
+        assert_eq!(
+            links.resolve(from, "setup#Install", true),
+            ("guide/setup.svg#install".to_string(), None)
+        );
+        assert_eq!(
+            links.resolve(from, "page name", true),
+            ("Page%20Name.svg".to_string(), None)
+        );
+        assert_eq!(
+            links.resolve(from, "#home", false),
+            ("#home".to_string(), None)
+        );
+        assert_eq!(
+            links.resolve(from, "https://example.com/a.md", false),
+            ("https://example.com/a.md".to_string(), None)
+        );
+        assert_eq!(
+            links.resolve(from, "notes.txt", false),
+            ("notes.txt".to_string(), None)
+        );
+
+        for (dest, wiki) in [
+            ("missing.md", false),
+            ("guide/setup.md#uninstall", false),
+            ("Nowhere", true),
+            ("#away", false),
+        ] {
+            let (href, warning) = links.resolve(from, dest, wiki);
+            assert_eq!(href, dest);
+            assert!(warning.is_some(), "{dest}");
+        }
+    }
+
+    #[test]
+    fn links_to_paged_documents_name_the_page_of_their_heading() {
+        let mut links = LinkMap::new();
+        links.add("index.md", "index.svg", "# Home\n## Later\n");
+        links.add("guide.md", "guide.svg", "# Guide\n## Install\n");
+        links.set_pages(
+            "guide.md",
+            vec!["guide-1.svg".into(), "guide-2.svg".into()],
+            HashMap::from([("guide".to_string(), 0), ("install".to_string(), 1)]),
+        );
+        links.set_pages(
+            "index.md",
+            vec!["index-1.svg".into(), "index-2.svg".into()],
+            HashMap::from([("home".to_string(), 0), ("later".to_string(), 1)]),
+        );
+
+        let from = Path::new("index.md");
+        assert_eq!(
+            links.resolve(from, "guide.md#install", false).0,
+            "guide-2.svg#install"
+        );
+        assert_eq!(links.resolve(from, "Guide", true).0, "guide-1.svg");
+        assert_eq!(links.resolve(from, "#later", false).0, "index-2.svg#later");
+    }
+}
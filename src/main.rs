@@ -1,6 +1,9 @@
 use clap::{CommandFactory, Parser};
+use markie::links::LinkMap;
 use markie::{fonts, renderer, theme};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 /// A pure Rust Markdown to SVG/PNG/PDF renderer
 #[derive(Parser, Debug)]
@@ -8,14 +11,21 @@ use std::path::{Path, PathBuf};
 #[command(version)]
 #[command(about = "Render Markdown to beautiful SVG, PNG or PDF images", long_about = None)]
 struct Args {
-    /// Input markdown file (use "-" for stdin)
+    /// Input markdown file (use "-" for stdin), or a directory to render
+    /// every markdown file in it with links between them resolved; with
+    /// --book, the chapter files in order, a SUMMARY.md or a book manifest
     #[arg(value_name = "INPUT", required_unless_present_any = ["completions", "list_themes"])]
-    input: Option<PathBuf>,
+    inputs: Vec<PathBuf>,
 
-    /// Output file path (extension determines format: .svg, .png or .pdf) [default: INPUT.png]
+    /// Output file path (extension determines format: .svg, .png or .pdf) [default: INPUT.png];
+    /// for a directory INPUT, the output directory [default: INPUT]
     #[arg(short, long, value_name = "OUTPUT")]
     output: Option<PathBuf>,
 
+    /// Output format for files rendered from a directory INPUT
+    #[arg(long, value_name = "FORMAT", default_value = "png", value_parser = ["svg", "png", "pdf"])]
+    format: String,
+
     /// Theme name or path to Alacritty theme file (YAML or TOML)
     #[arg(short, long, value_name = "THEME")]
     theme: Option<String>,
@@ -32,6 +42,81 @@ struct Args {
     #[arg(long, default_value_t = 2.0)]
     png_scale: f32,
 
+    /// Embed subsetted copies of the measured fonts into SVG output so it renders
+    /// identically in every browser
+    #[arg(long)]
+    embed_fonts: bool,
+
+    /// Resample embedded raster images to their drawn size × --png-scale
+    #[arg(long)]
+    downscale_images: bool,
+
+    /// Resample embedded raster images to at most this many pixels per inch
+    /// (96 DPI is one pixel per SVG unit); implies --downscale-images
+    #[arg(long, value_name = "DPI")]
+    max_dpi: Option<f32>,
+
+    /// Re-encode opaque embedded raster images as JPEG at this quality (1-100)
+    #[arg(long, value_name = "QUALITY", value_parser = clap::value_parser!(u8).range(1..=100))]
+    jpeg_quality: Option<u8>,
+
+    /// Split the output into pages of this height with running headers and
+    /// footers: pixels, or "a4"/"letter" for that paper's shape at --width.
+    /// PDF output gets one page each; SVG and PNG output one numbered file
+    /// per page
+    #[arg(long, value_name = "HEIGHT")]
+    page_height: Option<String>,
+
+    /// Render a slide deck: one slide per page, split at thematic breaks
+    /// (or at H1/H2 headings when there are none), each centered and scaled
+    /// to fit a page of this aspect ratio, given as --slides=4:3 [default: 16:9]
+    #[arg(
+        long,
+        value_name = "RATIO",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "16:9",
+        value_parser = ["16:9", "4:3"],
+        conflicts_with = "page_height"
+    )]
+    slides: Option<String>,
+
+    /// Render the inputs as one book: a cover, a table of contents and every
+    /// chapter, paginated (at --page-height, a4 by default) and numbered
+    /// throughout, with the headings as PDF bookmarks [default output: INPUT.pdf]
+    #[arg(long, conflicts_with = "slides")]
+    book: bool,
+
+    /// Flow the document through this many balanced columns
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..=8))]
+    columns: Option<u8>,
+
+    /// Number block images as "Figure N" in their captions
+    #[arg(long)]
+    figure_numbers: bool,
+
+    /// Show <details> sections as their summary alone, without the body
+    #[arg(long)]
+    collapse_details: bool,
+
+    /// Prefix headings with section numbers (1, 1.1, 1.1.1), starting at
+    /// heading LEVEL given as --number-headings=LEVEL [default: 1]
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        num_args = 0..=1,
+        require_equals = true,
+        value_parser = clap::value_parser!(u8).range(1..=6)
+    )]
+    number_headings: Option<Option<u8>>,
+
+    /// Heading number template: the letter n in curly braces stands for the
+    /// section number, and the text around it is kept (e.g. a trailing dot)
+    // clap turns a literal `{n}` in help text into a line break, so the
+    // placeholder is described rather than shown.
+    #[arg(long, value_name = "FORMAT")]
+    heading_number_format: Option<String>,
+
     /// Generate shell completions and exit
     #[arg(long, value_name = "SHELL")]
     completions: Option<clap_complete::Shell>,
@@ -61,8 +146,65 @@ fn run() -> Result<(), String> {
     }
 
     let input = args
-        .input
+        .inputs
+        .first()
+        .cloned()
         .expect("input is required unless --completions or --list-themes is used");
+    if args.inputs.len() > 1 && !args.book {
+        return Err("Several inputs can only be rendered together with --book".to_string());
+    }
+
+    let mut theme = load_theme(args.theme.as_deref())?;
+    if let Some(columns) = args.columns {
+        theme.columns = columns;
+    }
+    if args.figure_numbers {
+        theme.figure_numbers = true;
+    }
+    if args.collapse_details {
+        theme.collapse_details = true;
+    }
+    if let Some(start) = args.number_headings {
+        theme.number_headings = true;
+        if let Some(start) = start {
+            theme.heading_number_start = start;
+        }
+    }
+    if let Some(format) = &args.heading_number_format {
+        theme.heading_number_format = format.clone();
+    }
+    let max_density = match args.max_dpi {
+        Some(dpi) if dpi > 0.0 => Some(dpi / 96.0),
+        Some(dpi) => return Err(format!("--max-dpi must be positive, got {}", dpi)),
+        None => args.downscale_images.then_some(args.png_scale),
+    };
+    let image_options = markie::images::ImageOptions {
+        max_density,
+        jpeg_quality: args.jpeg_quality,
+    };
+    let export_options = markie::export::ExportOptions {
+        png_scale: args.png_scale,
+        embed_fonts: args.embed_fonts,
+    };
+
+    if args.book {
+        return render_book(&args, theme, image_options, &export_options);
+    }
+
+    if input.is_dir() {
+        let output = args.output.clone().unwrap_or_else(|| input.clone());
+        return render_directory(
+            &input,
+            &output,
+            &args,
+            &theme,
+            image_options,
+            &export_options,
+        );
+    }
+
+    let page_height = page_height(&args)?;
+    let slide_aspect = slide_aspect(&args)?;
     let output = args.output.unwrap_or_else(|| {
         if input.to_str() == Some("-") {
             PathBuf::from("output.png")
@@ -71,35 +213,6 @@ fn run() -> Result<(), String> {
         }
     });
 
-    // Load theme: try built-in name first, then file path
-    let theme = if let Some(ref theme_arg) = args.theme {
-        if let Ok(builtin) = theme::Theme::from_builtin(theme_arg) {
-            builtin
-        } else {
-            let theme_path = Path::new(theme_arg);
-            if theme_path.exists() && theme_path.is_file() {
-                let content = std::fs::read_to_string(theme_path)
-                    .map_err(|e| format!("Failed to read theme file: {}", e))?;
-
-                // Try TOML first (since Alacritty is moving to TOML), then YAML
-                if let Ok(theme) = theme::Theme::from_alacritty_toml(&content) {
-                    theme
-                } else if let Ok(theme) = theme::Theme::from_alacritty_yaml(&content) {
-                    theme
-                } else {
-                    return Err("Failed to parse theme file as TOML or YAML".to_string());
-                }
-            } else {
-                return Err(format!(
-                    "Unknown theme '{}'. Use --list-themes to see built-in themes, or provide a valid file path.",
-                    theme_arg
-                ));
-            }
-        }
-    } else {
-        theme::Theme::default()
-    };
-
     // Read markdown input
     let markdown = if input.to_str() == Some("-") {
         let mut buffer = String::new();
@@ -117,13 +230,289 @@ fn run() -> Result<(), String> {
     };
 
     // Render to SVG
-    let measure = fonts::CosmicTextMeasure::new()?;
+    let measure = fonts::CosmicTextMeasure::with_fallbacks(&theme.fonts)?;
     let mut renderer =
         renderer::Renderer::new_with_base_path(theme, measure, args.width, base_path)?;
+    renderer.set_image_options(image_options);
+    if let Some(aspect) = slide_aspect {
+        let slides = renderer.render_slides(&markdown, aspect)?;
+        return markie::export::save_pages(&slides, &[], &output, &export_options);
+    }
+    if let Some(page_height) = page_height {
+        let pages = renderer.render_pages(&markdown, page_height)?;
+        return markie::export::save_pages(
+            std::slice::from_ref(&pages),
+            &[],
+            &output,
+            &export_options,
+        );
+    }
     let svg = renderer.render(&markdown)?;
 
     // Save output in the requested format
-    markie::export::save_output(&svg, &output, args.png_scale)?;
+    markie::export::save_output_with_options(&svg, &output, &export_options)?;
+
+    Ok(())
+}
+
+/// Load a theme: try a built-in name first, then a file path.
+fn load_theme(theme_arg: Option<&str>) -> Result<theme::Theme, String> {
+    let Some(theme_arg) = theme_arg else {
+        return Ok(theme::Theme::default());
+    };
+    if let Ok(builtin) = theme::Theme::from_builtin(theme_arg) {
+        return Ok(builtin);
+    }
+    let theme_path = Path::new(theme_arg);
+    if !(theme_path.exists() && theme_path.is_file()) {
+        return Err(format!(
+            "Unknown theme '{}'. Use --list-themes to see built-in themes, or provide a valid file path.",
+            theme_arg
+        ));
+    }
+    let content = std::fs::read_to_string(theme_path)
+        .map_err(|e| format!("Failed to read theme file: {}", e))?;
+
+    // Try TOML first (since Alacritty is moving to TOML), then YAML
+    if let Ok(theme) = theme::Theme::from_alacritty_toml(&content) {
+        Ok(theme)
+    } else if let Ok(theme) = theme::Theme::from_alacritty_yaml(&content) {
+        Ok(theme)
+    } else {
+        Err("Failed to parse theme file as TOML or YAML".to_string())
+    }
+}
+
+/// Render every markdown file under `input` into the same layout under
+/// `output`. Links between the files point at their rendered outputs.
+fn render_directory(
+    input: &Path,
+    output: &Path,
+    args: &Args,
+    theme: &theme::Theme,
+    image_options: markie::images::ImageOptions,
+    export_options: &markie::export::ExportOptions,
+) -> Result<(), String> {
+    let mut sources = Vec::new();
+    collect_markdown_files(input, Path::new(""), &mut sources)?;
+    if sources.is_empty() {
+        return Err(format!("No markdown files found in {}", input.display()));
+    }
+
+    let mut documents = Vec::with_capacity(sources.len());
+    let mut links = LinkMap::new();
+    for source in sources {
+        let path = input.join(&source);
+        let markdown = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let target = source.with_extension(&args.format);
+        links.add(&source, &target, &markdown);
+        documents.push((source, target, markdown));
+    }
+
+    // Paged SVG and PNG output is one file per page, so links need the page
+    // each heading lands on: lay every document out once to find it.
+    let paged = slide_aspect(args)?.is_some() || page_height(args)?.is_some();
+    if paged && args.format != "pdf" {
+        for (source, target, markdown) in &documents {
+            let path = input.join(source);
+            let mut renderer = directory_renderer(&path, args, theme, image_options)?;
+            let Some(pages) = render_paged(&mut renderer, markdown, args)
+                .map_err(|e| format!("{}: {}", path.display(), e))?
+            else {
+                continue;
+            };
+            let count: usize = pages.iter().map(|paged| paged.pages.len()).sum();
+            let anchor_pages = if let [document] = pages.as_slice() {
+                renderer
+                    .outline()
+                    .iter()
+                    .map(|entry| {
+                        let page = document
+                            .pages
+                            .iter()
+                            .position(|page| entry.y < page.range.end)
+                            .unwrap_or(count.saturating_sub(1));
+                        (entry.anchor.clone(), page)
+                    })
+                    .collect()
+            } else {
+                // Slides are laid out one by one; a heading is on the slide
+                // whose markdown has it.
+                let mut anchor_pages = HashMap::new();
+                for (index, slide) in markie::slides::split_slides(markdown).iter().enumerate() {
+                    for anchor in markie::links::anchors(slide) {
+                        anchor_pages.entry(anchor).or_insert(index);
+                    }
+                }
+                anchor_pages
+            };
+            links.set_pages(
+                source,
+                markie::export::page_paths(target, count),
+                anchor_pages,
+            );
+        }
+    }
+    let links = Rc::new(links);
 
+    for (source, target, markdown) in documents {
+        let path = input.join(&source);
+        let mut renderer = directory_renderer(&path, args, theme, image_options)?;
+        renderer.set_link_map(Rc::clone(&links), &source);
+        let in_file = |e| format!("{}: {}", path.display(), e);
+        let target = output.join(target);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                format!(
+                    "Failed to create output directory {}: {}",
+                    parent.display(),
+                    e
+                )
+            })?;
+        }
+        if let Some(pages) = render_paged(&mut renderer, &markdown, args).map_err(in_file)? {
+            markie::export::save_pages(&pages, &[], &target, export_options)?;
+        } else {
+            let svg = renderer.render(&markdown).map_err(in_file)?;
+            markie::export::save_output_with_options(&svg, &target, export_options)?;
+        }
+    }
     Ok(())
 }
+
+/// A renderer for the file at `path` in a rendered directory.
+fn directory_renderer(
+    path: &Path,
+    args: &Args,
+    theme: &theme::Theme,
+    image_options: markie::images::ImageOptions,
+) -> Result<renderer::Renderer<fonts::CosmicTextMeasure>, String> {
+    let measure = fonts::CosmicTextMeasure::with_fallbacks(&theme.fonts)?;
+    let mut renderer = renderer::Renderer::new_with_base_path(
+        theme.clone(),
+        measure,
+        args.width,
+        path.parent().map(Path::to_path_buf),
+    )?;
+    renderer.set_image_options(image_options);
+    Ok(renderer)
+}
+
+/// Render `markdown` as a slide deck with --slides or as pages with
+/// --page-height; `None` when the output is a single image.
+fn render_paged(
+    renderer: &mut renderer::Renderer<fonts::CosmicTextMeasure>,
+    markdown: &str,
+    args: &Args,
+) -> Result<Option<Vec<markie::pages::PagedDocument>>, String> {
+    if let Some(aspect) = slide_aspect(args)? {
+        return renderer.render_slides(markdown, aspect).map(Some);
+    }
+    if let Some(page_height) = page_height(args)? {
+        return renderer
+            .render_pages(markdown, page_height)
+            .map(|pages| Some(vec![pages]));
+    }
+    Ok(None)
+}
+
+/// Render the inputs as a book into one paginated output.
+fn render_book(
+    args: &Args,
+    theme: theme::Theme,
+    image_options: markie::images::ImageOptions,
+    export_options: &markie::export::ExportOptions,
+) -> Result<(), String> {
+    let book = markie::book::Book::load(&args.inputs)?;
+    let page_height = match page_height(args)? {
+        Some(height) => height,
+        None => markie::pages::parse_page_height("a4", args.width)?,
+    };
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.inputs[0].with_extension("pdf"));
+    let measure = fonts::CosmicTextMeasure::with_fallbacks(&theme.fonts)?;
+    let mut renderer = renderer::Renderer::new(theme, measure, args.width)?;
+    renderer.set_image_options(image_options);
+    let (documents, bookmarks) = renderer.render_book(&book, page_height)?;
+    markie::export::save_pages(&documents, &bookmarks, &output, export_options)
+}
+
+/// The --page-height in pixels, when output is paginated.
+fn page_height(args: &Args) -> Result<Option<f32>, String> {
+    args.page_height
+        .as_deref()
+        .map(|value| markie::pages::parse_page_height(value, args.width))
+        .transpose()
+}
+
+/// The --slides aspect ratio, when rendering a slide deck.
+fn slide_aspect(args: &Args) -> Result<Option<markie::slides::SlideAspect>, String> {
+    args.slides
+        .as_deref()
+        .map(markie::slides::SlideAspect::parse)
+        .transpose()
+}
+
+/// Collect `.md`/`.markdown` files under `root.join(dir)` as paths relative
+/// to `root`, in sorted order. Hidden entries are skipped.
+fn collect_markdown_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let full = root.join(dir);
+    let entries = std::fs::read_dir(&full)
+        .map_err(|e| format!("Failed to read directory {}: {}", full.display(), e))?;
+    let mut entries: Vec<_> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name())
+        .filter(|name| !name.to_string_lossy().starts_with('.'))
+        .collect();
+    entries.sort();
+    for name in entries {
+        let relative = dir.join(&name);
+        if root.join(&relative).is_dir() {
+            collect_markdown_files(root, &relative, files)?;
+        } else if relative
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown")
+            })
+        {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn number_headings_before_the_input_takes_no_level() {
+        let args = Args::try_parse_from(["markie", "--number-headings", "input.md"]).unwrap();
+        assert_eq!(args.number_headings, Some(None));
+        assert_eq!(args.inputs, vec![PathBuf::from("input.md")]);
+
+        let args = Args::try_parse_from(["markie", "--number-headings=2", "input.md"]).unwrap();
+        assert_eq!(args.number_headings, Some(Some(2)));
+    }
+
+    #[test]
+    fn slides_before_the_input_takes_no_ratio() {
+        let args =
+            Args::try_parse_from(["markie", "--slides", "deck.md", "-o", "deck.pdf"]).unwrap();
+        assert_eq!(args.slides.as_deref(), Some("16:9"));
+        assert_eq!(args.inputs, vec![PathBuf::from("deck.md")]);
+
+        let args = Args::try_parse_from(["markie", "--slides=4:3", "deck.md"]).unwrap();
+        assert_eq!(args.slides.as_deref(), Some("4:3"));
+    }
+
+    #[test]
+    fn heading_number_format_help_stays_on_one_line() {
+        let help = Args::command().render_help().to_string();
+        assert!(help.contains("the letter n in curly braces stands for the section number"));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::fonts::TextMeasure;
+use crate::fonts::{FontDescriptor, FontFamily, FontStyle, TextMeasure, TextMetrics};
 use latex2mathml::{DisplayStyle, latex_to_mathml};
 use quick_xml::events::Event as XmlEvent;
 use quick_xml::reader::Reader as XmlReader;
@@ -452,14 +452,26 @@ fn escape_xml(text: &str) -> String {
     crate::xml::escape_xml(text)
 }
 
+/// Measure a token in the family it is drawn with (`serif` for math symbols,
+/// `sans-serif` for `\text{}`).
 fn measure_token<T: TextMeasure>(
     text: &str,
     font_size: f32,
+    family: FontFamily,
     italic: bool,
     measure: &mut T,
-) -> (f32, f32) {
+) -> TextMetrics {
     let cleaned = crate::xml::sanitize_xml_text(text);
-    measure.measure_text(&cleaned, font_size, false, false, italic, None)
+    let font = FontDescriptor {
+        family,
+        style: if italic {
+            FontStyle::Italic
+        } else {
+            FontStyle::Normal
+        },
+        ..FontDescriptor::default()
+    };
+    measure.measure(&cleaned, &font, font_size, None)
 }
 
 fn layout_node<T: TextMeasure>(
@@ -473,7 +485,7 @@ fn layout_node<T: TextMeasure>(
     match node {
         MathNode::Ident(text) => {
             let italic = text.len() == 1 && text.chars().next().is_some_and(|c| c.is_alphabetic());
-            let (w, _h) = measure_token(text, font_size, italic, measure);
+            let metrics = measure_token(text, font_size, FontFamily::Serif, italic, measure);
             let style = if italic { " font-style=\"italic\"" } else { "" };
             let svg = format!(
                 r#"<text x="{:.2}" y="{:.2}" font-family="serif" font-size="{:.2}" fill="{}"{}>{}</text>"#,
@@ -485,14 +497,14 @@ fn layout_node<T: TextMeasure>(
                 escape_xml(text)
             );
             MathBox {
-                width: w,
-                ascent: font_size * 0.75,
-                descent: font_size * 0.25,
+                width: metrics.advance,
+                ascent: metrics.ascent,
+                descent: metrics.descent,
                 svg,
             }
         }
         MathNode::Number(text) => {
-            let (w, _h) = measure_token(text, font_size, false, measure);
+            let metrics = measure_token(text, font_size, FontFamily::Serif, false, measure);
             let svg = format!(
                 r#"<text x="{:.2}" y="{:.2}" font-family="serif" font-size="{:.2}" fill="{}">{}</text>"#,
                 x,
@@ -502,16 +514,17 @@ fn layout_node<T: TextMeasure>(
                 escape_xml(text)
             );
             MathBox {
-                width: w,
-                ascent: font_size * 0.75,
-                descent: font_size * 0.25,
+                width: metrics.advance,
+                ascent: metrics.ascent,
+                descent: metrics.descent,
                 svg,
             }
         }
         MathNode::Operator(text) => {
             let is_large = is_large_operator(text);
             let effective_size = if is_large { font_size * 1.4 } else { font_size };
-            let (w, _h) = measure_token(text, effective_size, false, measure);
+            let metrics = measure_token(text, effective_size, FontFamily::Serif, false, measure);
+            let w = metrics.advance;
             let spacing = font_size * 0.15;
             let total_w = w + spacing * 2.0;
             let y_offset = if is_large {
@@ -532,18 +545,18 @@ fn layout_node<T: TextMeasure>(
                 ascent: if is_large {
                     effective_size * 0.8
                 } else {
-                    font_size * 0.75
+                    metrics.ascent
                 },
                 descent: if is_large {
                     effective_size * 0.3
                 } else {
-                    font_size * 0.25
+                    metrics.descent
                 },
                 svg,
             }
         }
         MathNode::Text(text) => {
-            let (w, _h) = measure_token(text, font_size, false, measure);
+            let metrics = measure_token(text, font_size, FontFamily::SansSerif, false, measure);
             let svg = format!(
                 r#"<text x="{:.2}" y="{:.2}" font-family="sans-serif" font-size="{:.2}" fill="{}">{}</text>"#,
                 x,
@@ -553,9 +566,9 @@ fn layout_node<T: TextMeasure>(
                 escape_xml(text)
             );
             MathBox {
-                width: w,
-                ascent: font_size * 0.75,
-                descent: font_size * 0.25,
+                width: metrics.advance,
+                ascent: metrics.ascent,
+                descent: metrics.descent,
                 svg,
             }
         }
@@ -788,7 +801,7 @@ fn layout_node<T: TextMeasure>(
             layout_table(rows, column_align, font_size, color, measure, x, baseline_y)
         }
         MathNode::StretchyOp { op, form } => {
-            let (w, _h) = measure_token(op, font_size, false, measure);
+            let metrics = measure_token(op, font_size, FontFamily::Serif, false, measure);
             let offset = match form.as_str() {
                 "prefix" => font_size * 0.08,
                 "postfix" => -font_size * 0.08,
@@ -803,9 +816,9 @@ fn layout_node<T: TextMeasure>(
                 escape_xml(op)
             );
             MathBox {
-                width: w,
-                ascent: font_size * 0.75,
-                descent: font_size * 0.25,
+                width: metrics.advance,
+                ascent: metrics.ascent,
+                descent: metrics.descent,
                 svg,
             }
         }
@@ -259,8 +259,14 @@ fn render_node(label: &str, shape: &NodeShape, pos: &LayoutPos, style: &DiagramS
     for (i, line) in lines.iter().enumerate() {
         let y = start_y + i as f32 * line_height;
         svg.push_str(&format!(
-            r#"<text x="{:.2}" y="{:.2}" dy="0.35em" font-family="{}" font-size="{:.1}" font-weight="500" fill="{}" text-anchor="middle">{}</text>"#,
-            text_x, y, style.font_family, style.font_size, style.node_text, line
+            r#"<text x="{:.2}" y="{:.2}" dy="{:.3}em" font-family="{}" font-size="{:.1}" font-weight="500" fill="{}" text-anchor="middle">{}</text>"#,
+            text_x,
+            y,
+            style.text_center_em,
+            style.font_family,
+            style.font_size,
+            style.node_text,
+            line
         ));
     }
 
@@ -580,9 +586,10 @@ fn render_edge<T: TextMeasure>(ctx: &mut RenderEdgeContext<'_, T>) -> String {
         ));
 
         svg.push_str(&format!(
-            r#"<text x="{:.2}" y="{:.2}" dy="0.35em" font-family="{}" font-size="{:.1}" fill="{}" text-anchor="middle">{}</text>"#,
+            r#"<text x="{:.2}" y="{:.2}" dy="{:.3}em" font-family="{}" font-size="{:.1}" fill="{}" text-anchor="middle">{}</text>"#,
             label_x,
             label_y,
+            style.text_center_em,
             style.font_family,
             label_font_size,
             style.edge_text,
@@ -1,6 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
-use crate::fonts::TextMeasure;
+use crate::fonts::{FontDescriptor, TextMeasure};
 use crate::layout::Rect;
 
 use super::layout::{LayoutEngine, LayoutPos};
@@ -18,6 +18,9 @@ pub struct DiagramStyle {
     pub background: String,
     pub font_family: String,
     pub font_size: f32,
+    /// Baseline shift, in em, that vertically centers a label on its anchor
+    /// point. Derived from the measured font's ascent and descent.
+    pub text_center_em: f32,
 }
 
 impl Default for DiagramStyle {
@@ -31,6 +34,7 @@ impl Default for DiagramStyle {
             background: "transparent".to_string(),
             font_family: "sans-serif".to_string(),
             font_size: 13.0,
+            text_center_em: 0.35,
         }
     }
 }
@@ -52,6 +56,7 @@ impl DiagramStyle {
             background: background.to_string(),
             font_family: "sans-serif".to_string(),
             font_size: 13.0,
+            text_center_em: 0.35,
         }
     }
 }
@@ -122,6 +127,13 @@ pub fn render_diagram<T: TextMeasure>(
 ) -> Result<(String, f32, f32), String> {
     let diagram = parse_mermaid(source)?;
 
+    let mut style = style.clone();
+    let metrics = measure.font_metrics(&FontDescriptor::default(), style.font_size);
+    if style.font_size > 0.0 {
+        style.text_center_em = (metrics.ascent - metrics.descent) / (2.0 * style.font_size);
+    }
+    let style = &style;
+
     let result = match diagram {
         MermaidDiagram::Flowchart(fc) => super::flowchart::render_flowchart(&fc, style, measure)?,
         MermaidDiagram::Sequence(seq) => render_sequence(&seq, style, measure)?,
@@ -212,9 +224,10 @@ fn render_sequence(
                 pos.x, pos.y, pos.width, pos.height, style.node_fill, style.node_stroke
             ));
             svg.push_str(&format!(
-                r#"<text x="{:.2}" y="{:.2}" dy="0.35em" font-family="{}" font-size="{:.1}" font-weight="500" fill="{}" text-anchor="middle">{}</text>"#,
+                r#"<text x="{:.2}" y="{:.2}" dy="{:.3}em" font-family="{}" font-size="{:.1}" font-weight="500" fill="{}" text-anchor="middle">{}</text>"#,
                 text_x,
                 pos.y + pos.height / 2.0,
+                style.text_center_em,
                 style.font_family,
                 style.font_size,
                 style.node_text,
@@ -353,9 +366,10 @@ fn render_sequence_elements<T: TextMeasure>(ctx: &mut RenderSequenceContext<'_,
                                 style.node_fill
                             ));
                             svg.push_str(&format!(
-                                r#"<text x="{:.2}" y="{:.2}" dy="0.35em" font-family="{}" font-size="{:.1}" fill="{}">{}</text>"#,
+                                r#"<text x="{:.2}" y="{:.2}" dy="{:.3}em" font-family="{}" font-size="{:.1}" fill="{}">{}</text>"#,
                                 lx,
                                 ly,
+                                style.text_center_em,
                                 style.font_family,
                                 label_font,
                                 style.edge_text,
@@ -719,9 +733,10 @@ fn render_class_box(class: &ClassDefinition, pos: &LayoutPos, style: &DiagramSty
     ));
 
     svg.push_str(&format!(
-        r#"<text x="{:.2}" y="{:.2}" dy="0.35em" font-family="{}" font-size="{:.1}" fill="{}" text-anchor="middle" font-weight="bold"{}>{}</text>"#,
+        r#"<text x="{:.2}" y="{:.2}" dy="{:.3}em" font-family="{}" font-size="{:.1}" fill="{}" text-anchor="middle" font-weight="bold"{}>{}</text>"#,
         pos.x + pos.width / 2.0,
         pos.y + header_h / 2.0,
+        style.text_center_em,
         style.font_family,
         style.font_size,
         style.node_text,
@@ -1277,7 +1292,7 @@ fn render_state_node(
         let text_y = if state.is_composite {
             pos.y + style.font_size + 8.0
         } else {
-            pos.y + pos.height / 2.0 + style.font_size / 3.0
+            pos.y + pos.height / 2.0 + style.font_size * style.text_center_em
         };
         svg.push_str(&format!(
             r#"<text x="{:.2}" y="{:.2}" font-family="{}" font-size="{:.1}" fill="{}" text-anchor="middle">{}</text>"#,
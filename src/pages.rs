@@ -0,0 +1,552 @@
+//! Paginated output: splitting a rendered document into pages and filling
+//! the running header and footer templates.
+//!
+//! The renderer lays a document out as one tall page and records where a
+//! page may end (below a line, block, table row or code line). Pages are
+//! cut at the last such point that fits, so lines are never split unless a
+//! single block is taller than a page.
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// A document split into pages.
+#[derive(Debug, Clone)]
+pub struct PagedDocument {
+    /// Page size in pixels.
+    pub width: f32,
+    pub page_height: f32,
+    /// The whole document as one SVG, which every page shows part of.
+    pub document: String,
+    pub pages: Vec<Page>,
+    /// Where links to the other documents of the same PDF lead, by href:
+    /// the index of the target document and the anchor in it (empty for
+    /// its top). Links to `#anchor` lead within the document itself.
+    pub link_targets: HashMap<String, (usize, String)>,
+}
+
+/// One page: a slice of the document inside a frame.
+#[derive(Debug, Clone)]
+pub struct Page {
+    /// The part of the document shown.
+    pub range: Range<f32>,
+    /// Where on the page the top of `range` is drawn.
+    pub top: f32,
+    /// The page's background, header and footer, as an SVG the size of the
+    /// page.
+    pub frame: String,
+}
+
+/// An entry of the PDF bookmark tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    /// Nesting depth, 1 for the top level.
+    pub level: u8,
+    pub title: String,
+    /// Index of the page in the output, counting from 0.
+    pub page: usize,
+    /// Distance from the top of the page.
+    pub y: f32,
+}
+
+impl PagedDocument {
+    /// A document drawn on one page `width` × `height`, over a plain
+    /// `background`.
+    pub fn single_page(width: f32, height: f32, document: String, background: &str) -> Self {
+        Self {
+            width,
+            page_height: height,
+            document,
+            pages: vec![Page {
+                range: 0.0..height,
+                top: 0.0,
+                frame: format!(
+                    r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {0} {1}" width="{0}" height="{1}"><rect width="100%" height="100%" fill="{2}" /></svg>"#,
+                    width, height, background
+                ),
+            }],
+            link_targets: HashMap::new(),
+        }
+    }
+
+    /// Each page as a standalone SVG, with its slice of the document in a
+    /// nested viewport. Only the document's top-level elements that reach
+    /// into the slice are copied; elements of unknown extent (and
+    /// definitions) go on every page.
+    pub fn page_svgs(&self) -> Vec<String> {
+        let inner = svg_inner(&self.document);
+        let elements = top_level_extents(inner);
+        self.pages
+            .iter()
+            .map(|page| {
+                let content = match &elements {
+                    Some(elements) => {
+                        let mut content = String::new();
+                        for (markup, extent) in elements {
+                            if extent.reaches(&page.range) {
+                                content.push_str(&inner[markup.clone()]);
+                            }
+                        }
+                        std::borrow::Cow::Owned(content)
+                    }
+                    None => std::borrow::Cow::Borrowed(inner),
+                };
+                let height = page.range.end - page.range.start;
+                let frame = page.frame.trim_end();
+                let frame = frame.strip_suffix("</svg>").unwrap_or(frame);
+                format!(
+                    r#"{}<svg x="0" y="{:.2}" width="{}" height="{:.2}" viewBox="0 {:.2} {} {:.2}">{}</svg></svg>"#,
+                    frame,
+                    page.top,
+                    self.width,
+                    height,
+                    page.range.start,
+                    self.width,
+                    height,
+                    content,
+                )
+            })
+            .collect()
+    }
+}
+
+/// An `<a>` start tag in a rendered SVG, with its `id` and `href`.
+pub(crate) struct LinkTag {
+    /// Byte range of the whole start tag.
+    pub range: Range<usize>,
+    pub id: Option<String>,
+    pub href: Option<String>,
+}
+
+/// Find every `<a>` start tag written by the renderer. The SVG is our own
+/// output, so attributes are always double-quoted.
+pub(crate) fn link_tags(svg: &str) -> Vec<LinkTag> {
+    let mut tags = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = svg[pos..].find("<a ") {
+        let start = pos + found;
+        let Some(len) = svg[start..].find('>') else {
+            break;
+        };
+        pos = start + len + 1;
+        let tag = &svg[start..pos];
+        let attr = |name: &str| {
+            let needle = format!(" {}=\"", name);
+            let value_start = tag.find(&needle)? + needle.len();
+            let value = &tag[value_start..value_start + tag[value_start..].find('"')?];
+            Some(
+                quick_xml::escape::unescape(value)
+                    .map(|value| value.into_owned())
+                    .unwrap_or_else(|_| value.to_string()),
+            )
+        };
+        tags.push(LinkTag {
+            range: start..pos,
+            id: attr("id"),
+            href: attr("href"),
+        });
+    }
+    tags
+}
+
+/// How far down the document an element reaches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Extent {
+    /// Draws nothing by itself (a group without children, `<defs>`).
+    Empty,
+    /// Covers `top..bottom`, give or take a stroke.
+    Span(f32, f32),
+    /// Could be anywhere.
+    Unknown,
+}
+
+impl Extent {
+    fn union(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Unknown, _) | (_, Self::Unknown) => Self::Unknown,
+            (Self::Empty, extent) | (extent, Self::Empty) => extent,
+            (Self::Span(a, b), Self::Span(c, d)) => Self::Span(a.min(c), b.max(d)),
+        }
+    }
+
+    /// The extent after `y ↦ scale·y + offset`.
+    fn map(self, scale: f32, offset: f32) -> Self {
+        match self {
+            Self::Span(top, bottom) => {
+                let (a, b) = (scale * top + offset, scale * bottom + offset);
+                Self::Span(a.min(b), a.max(b))
+            }
+            extent => extent,
+        }
+    }
+
+    fn from_ys(ys: impl IntoIterator<Item = f32>) -> Self {
+        ys.into_iter()
+            .fold(Self::Empty, |extent, y| extent.union(Self::Span(y, y)))
+    }
+
+    /// Whether an element of this extent may show on a page showing
+    /// `range`. Spans are widened a little for strokes and glyph overhang.
+    fn reaches(&self, range: &Range<f32>) -> bool {
+        const SLACK: f32 = 2.0;
+        match *self {
+            Self::Span(top, bottom) => top < range.end + SLACK && bottom > range.start - SLACK,
+            Self::Empty | Self::Unknown => true,
+        }
+    }
+}
+
+/// The byte range and vertical extent of every top-level element of an SVG
+/// fragment, or `None` if it does not parse.
+fn top_level_extents(inner: &str) -> Option<Vec<(Range<usize>, Extent)>> {
+    struct Open {
+        start: usize,
+        own: Extent,
+        /// `Some((scale, offset))` for containers whose children count.
+        transform: Option<(f32, f32)>,
+        children: Extent,
+    }
+
+    let mut reader = Reader::from_str(inner);
+    let mut stack: Vec<Open> = Vec::new();
+    let mut elements = Vec::new();
+    let mut close = |stack: &mut Vec<Open>, open: Open, end: usize| {
+        let extent = match open.transform {
+            Some((scale, offset)) => open.own.union(open.children.map(scale, offset)),
+            None => open.own,
+        };
+        match stack.last_mut() {
+            Some(parent) => parent.children = parent.children.union(extent),
+            None => elements.push((open.start..end, extent)),
+        }
+    };
+    loop {
+        let start = reader.buffer_position() as usize;
+        match reader.read_event().ok()? {
+            Event::Start(tag) => {
+                let (own, transform) = element_extent(&tag);
+                stack.push(Open {
+                    start,
+                    own,
+                    transform,
+                    children: Extent::Empty,
+                });
+            }
+            Event::Empty(tag) => {
+                let (own, _) = element_extent(&tag);
+                let open = Open {
+                    start,
+                    own,
+                    transform: None,
+                    children: Extent::Empty,
+                };
+                close(&mut stack, open, reader.buffer_position() as usize);
+            }
+            Event::End(_) => {
+                let open = stack.pop()?;
+                close(&mut stack, open, reader.buffer_position() as usize);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    stack.is_empty().then_some(elements)
+}
+
+/// An element's own extent, and for containers the transform their
+/// children are drawn through.
+fn element_extent(tag: &BytesStart) -> (Extent, Option<(f32, f32)>) {
+    let attr = |name: &str| {
+        tag.attributes()
+            .filter_map(Result::ok)
+            .find(|a| a.key.as_ref() == name.as_bytes())
+            .map(|a| String::from_utf8_lossy(&a.value).into_owned())
+    };
+    let number = |name: &str, default: f32| match attr(name) {
+        Some(value) => numbers(&value).and_then(|n| n.first().copied()),
+        None => Some(default),
+    };
+    let extent = match tag.name().as_ref() {
+        b"g" | b"a" | b"switch" => {
+            let transform = match attr("transform") {
+                Some(value) => y_transform(&value),
+                None => Some((1.0, 0.0)),
+            };
+            return match transform {
+                Some(transform) => (Extent::Empty, Some(transform)),
+                None => (Extent::Unknown, None),
+            };
+        }
+        b"defs" | b"symbol" | b"clipPath" | b"mask" | b"marker" | b"pattern"
+        | b"linearGradient" | b"radialGradient" | b"filter" | b"style" | b"title" | b"desc"
+        | b"metadata" => Some(Extent::Empty),
+        b"text" => number("y", 0.0)
+            .zip(number("font-size", 16.0))
+            .map(|(y, size)| Extent::Span(y - size, y + size * 0.35)),
+        b"rect" | b"image" | b"svg" | b"use" | b"foreignObject" => {
+            let height = attr("height").filter(|h| !h.ends_with('%'));
+            number("y", 0.0)
+                .zip(height.as_deref().and_then(numbers))
+                .and_then(|(y, height)| Some(Extent::Span(y, y + height.first()?)))
+        }
+        b"line" => number("y1", 0.0)
+            .zip(number("y2", 0.0))
+            .map(|(a, b)| Extent::from_ys([a, b])),
+        b"circle" => number("cy", 0.0)
+            .zip(number("r", 0.0))
+            .map(|(cy, r)| Extent::Span(cy - r, cy + r)),
+        b"ellipse" => number("cy", 0.0)
+            .zip(number("ry", 0.0))
+            .map(|(cy, r)| Extent::Span(cy - r, cy + r)),
+        b"polygon" | b"polyline" => attr("points")
+            .as_deref()
+            .and_then(numbers)
+            .map(|n| Extent::from_ys(n.iter().skip(1).step_by(2).copied())),
+        b"path" => attr("d").as_deref().and_then(path_extent),
+        _ => None,
+    };
+    (extent.unwrap_or(Extent::Unknown), None)
+}
+
+/// Numbers separated by spaces and commas.
+fn numbers(value: &str) -> Option<Vec<f32>> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.trim_end_matches("px").parse().ok())
+        .collect()
+}
+
+/// The vertical part of a `transform` made of translations and scales, as
+/// `(scale, offset)`; `None` for anything that mixes in x.
+fn y_transform(value: &str) -> Option<(f32, f32)> {
+    let mut ops = Vec::new();
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        let open = rest.find('(')?;
+        let close = rest.find(')')?;
+        let args = numbers(&rest[open + 1..close])?;
+        ops.push((rest[..open].trim().to_string(), args));
+        rest = rest[close + 1..].trim_start_matches([',', ' ']);
+    }
+    // The last operation applies first.
+    let (mut scale, mut offset) = (1.0, 0.0);
+    for (op, args) in ops.iter().rev() {
+        match (op.as_str(), args.as_slice()) {
+            ("translate", [_]) => {}
+            ("translate", [_, ty]) => offset += ty,
+            ("scale", [s]) | ("scale", [_, s]) => {
+                scale *= s;
+                offset *= s;
+            }
+            _ => return None,
+        }
+    }
+    Some((scale, offset))
+}
+
+/// The vertical extent of path data in absolute commands (control points
+/// included, which bound the curve); `None` for relative commands.
+fn path_extent(d: &str) -> Option<Extent> {
+    let mut extent = Extent::Empty;
+    let mut rest = d.trim();
+    while let Some(command) = rest.chars().next() {
+        let args_end = rest[1..]
+            .find(|c: char| c.is_ascii_alphabetic() && c != 'e')
+            .map_or(rest.len(), |i| i + 1);
+        let args = numbers(&rest[1..args_end])?;
+        let ys: Vec<f32> = match command {
+            'M' | 'L' | 'T' | 'C' | 'S' | 'Q' => args.iter().skip(1).step_by(2).copied().collect(),
+            'V' => args.clone(),
+            'H' | 'Z' => Vec::new(),
+            // rx ry rotation large-arc sweep x y: the arc stays within ry
+            // of its end points.
+            'A' => args
+                .chunks(7)
+                .filter(|arc| arc.len() == 7)
+                .flat_map(|arc| [arc[6] - arc[1], arc[6] + arc[1]])
+                .collect(),
+            _ => return None,
+        };
+        extent = extent.union(Extent::from_ys(ys));
+        rest = rest[args_end..].trim_start();
+    }
+    Some(extent)
+}
+
+/// The content of an `<svg>` document, without the root element's tags.
+pub(crate) fn svg_inner(svg: &str) -> &str {
+    let start = svg
+        .find("<svg")
+        .and_then(|start| svg[start..].find('>').map(|end| start + end + 1))
+        .unwrap_or(0);
+    let end = svg
+        .rfind("</svg>")
+        .filter(|&end| end >= start)
+        .unwrap_or(svg.len());
+    &svg[start..end]
+}
+
+/// Values substituted into header and footer templates: `{title}`,
+/// `{section}`, `{page}`, `{pages}` and `{date}`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageFields {
+    /// Front matter `title`, or the first H1.
+    pub title: String,
+    /// The H1/H2 section the page is in, with its number.
+    pub section: String,
+    /// 1-based page number.
+    pub page: usize,
+    pub pages: usize,
+    /// Front matter `date`, or today's date (`YYYY-MM-DD`, UTC).
+    pub date: String,
+}
+
+impl PageFields {
+    pub fn expand(&self, template: &str) -> String {
+        template
+            .replace("{title}", &self.title)
+            .replace("{section}", &self.section)
+            .replace("{pages}", &self.pages.to_string())
+            .replace("{page}", &self.page.to_string())
+            .replace("{date}", &self.date)
+    }
+}
+
+/// Parse a `--page-height` value: a height in pixels, or `a4`/`letter` for
+/// the paper's aspect ratio at `width`.
+pub fn parse_page_height(value: &str, width: f32) -> Result<f32, String> {
+    let height = match value.trim().to_ascii_lowercase().as_str() {
+        "a4" => width * 297.0 / 210.0,
+        "letter" => width * 11.0 / 8.5,
+        other => other
+            .strip_suffix("px")
+            .unwrap_or(other)
+            .parse()
+            .map_err(|_| format!("Invalid page height '{}' (use pixels, a4 or letter)", value))?,
+    };
+    if !(height > 0.0 && height.is_finite()) {
+        return Err(format!("Page height must be positive, got {}", value));
+    }
+    Ok(height)
+}
+
+/// Split `start..end` into ranges at most `height` long, each ending at the
+/// last of `breaks` that fits, or cut at `height` when none does.
+pub fn page_ranges(breaks: &[f32], start: f32, end: f32, height: f32) -> Vec<Range<f32>> {
+    let height = height.max(1.0);
+    let mut ranges = Vec::new();
+    let mut top = start;
+    while end - top > height {
+        let limit = top + height;
+        let cut = breaks
+            .iter()
+            .copied()
+            .filter(|&y| y > top + 1.0 && y <= limit)
+            .fold(None, |best: Option<f32>, y| {
+                Some(best.map_or(y, |b| b.max(y)))
+            })
+            .unwrap_or(limit);
+        ranges.push(top..cut);
+        top = cut;
+    }
+    ranges.push(top..end.max(top));
+    ranges
+}
+
+/// Today's date as `YYYY-MM-DD` (UTC).
+pub fn today() -> String {
+    let days = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86_400)
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Gregorian date of a day count since 1970-01-01 (Howard Hinnant's
+/// algorithm).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_end_at_the_last_break_that_fits() {
+        let breaks = [30.0, 90.0, 140.0, 260.0];
+        assert_eq!(
+            page_ranges(&breaks, 10.0, 300.0, 100.0),
+            vec![10.0..90.0, 90.0..140.0, 140.0..240.0, 240.0..300.0]
+        );
+        assert_eq!(page_ranges(&breaks, 10.0, 50.0, 100.0), vec![10.0..50.0]);
+    }
+
+    #[test]
+    fn expands_templates_and_page_sizes() {
+        let fields = PageFields {
+            title: "Guide".to_string(),
+            section: "2 Setup".to_string(),
+            page: 3,
+            pages: 12,
+            date: "2024-05-01".to_string(),
+        };
+        assert_eq!(
+            fields.expand("{title} · {section} · {page}/{pages} · {date}"),
+            "Guide · 2 Setup · 3/12 · 2024-05-01"
+        );
+        assert_eq!(parse_page_height("600", 800.0), Ok(600.0));
+        assert!((parse_page_height("A4", 210.0).unwrap() - 297.0).abs() < 0.01);
+        assert!(parse_page_height("tall", 800.0).is_err());
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_844), (2024, 5, 1));
+    }
+
+    #[test]
+    fn page_svgs_copy_only_the_elements_on_the_page() {
+        let document = concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="400">"#,
+            r#"<defs><clipPath id="c"><rect width="1" height="1" /></clipPath></defs>"#,
+            r#"<text x="0" y="50" font-size="16">first</text>"#,
+            r#"<a href="x"><rect x="0" y="150" width="10" height="20" /></a>"#,
+            r#"<g transform="translate(10, 200) scale(0.5)"><path d="M 0 100 L 5 160" /></g>"#,
+            r#"<text x="0" y="350" font-size="16">second</text>"#,
+            r#"<g transform="rotate(-90)"><text y="1">turned</text></g>"#,
+            "</svg>"
+        );
+        let paged = PagedDocument {
+            width: 100.0,
+            page_height: 200.0,
+            document: document.to_string(),
+            pages: [0.0..200.0, 200.0..400.0]
+                .into_iter()
+                .map(|range| Page {
+                    range,
+                    top: 0.0,
+                    frame: "<svg></svg>".to_string(),
+                })
+                .collect(),
+            link_targets: HashMap::new(),
+        };
+        let pages = paged.page_svgs();
+        assert!(pages[0].contains(">first<") && pages[0].contains("y=\"150\""));
+        assert!(!pages[0].contains(">second<") && !pages[0].contains("<path"));
+        assert!(pages[1].contains(">second<") && pages[1].contains("<path"));
+        assert!(!pages[1].contains(">first<") && !pages[1].contains("y=\"150\""));
+        for page in &pages {
+            assert!(page.contains("<clipPath") && page.contains(">turned<"));
+        }
+    }
+}
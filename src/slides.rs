@@ -0,0 +1,147 @@
+//! Slide decks: splitting markdown into slides and fitting each slide onto
+//! a fixed-size page.
+//!
+//! Slides are separated by thematic breaks (`---`), or, in a deck without
+//! any, start at each H1 and H2. Every slide is laid out as its own
+//! document and then scaled down (never up) and centered on the page.
+
+use crate::pages::PagedDocument;
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag};
+use std::ops::Range;
+
+/// A slide's shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlideAspect {
+    /// 16:9
+    Wide,
+    /// 4:3
+    Standard,
+}
+
+impl SlideAspect {
+    /// Parse `16:9` or `4:3`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "16:9" => Ok(Self::Wide),
+            "4:3" => Ok(Self::Standard),
+            other => Err(format!(
+                "Invalid slide aspect ratio '{}' (use 16:9 or 4:3)",
+                other
+            )),
+        }
+    }
+
+    /// Slide height for a slide `width` wide.
+    pub fn height(self, width: f32) -> f32 {
+        match self {
+            Self::Wide => width * 9.0 / 16.0,
+            Self::Standard => width * 3.0 / 4.0,
+        }
+    }
+}
+
+/// Split a deck into the markdown of each slide. Front matter is repeated
+/// at the top of every slide so its settings apply throughout; slides with
+/// nothing but whitespace are dropped.
+pub fn split_slides(markdown: &str) -> Vec<String> {
+    let mut front_matter = 0..0;
+    let mut rules = Vec::new();
+    let mut headings = Vec::new();
+    let mut depth = 0usize;
+    for (event, range) in
+        Parser::new_ext(markdown, crate::renderer::markdown_options()).into_offset_iter()
+    {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) if depth == 0 && range.start == 0 => {
+                front_matter = range;
+                depth += 1;
+            }
+            Event::Start(Tag::Heading { level, .. }) if depth == 0 => {
+                if level <= HeadingLevel::H2 {
+                    headings.push(range.start..range.start);
+                }
+                depth += 1;
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            Event::Rule if depth == 0 => rules.push(range),
+            _ => {}
+        }
+    }
+
+    let separators: Vec<Range<usize>> = if rules.is_empty() { headings } else { rules };
+    let header = markdown[front_matter.clone()].trim_end();
+    let mut start = front_matter.end;
+    let mut slides = Vec::new();
+    for separator in separators
+        .into_iter()
+        .chain(std::iter::once(markdown.len()..markdown.len()))
+    {
+        let body = &markdown[start..separator.start.max(start)];
+        if !body.trim().is_empty() {
+            slides.push(if header.is_empty() {
+                body.to_string()
+            } else {
+                format!("{}\n\n{}", header, body.trim_start_matches(['\r', '\n']))
+            });
+        }
+        start = separator.end.max(start);
+    }
+    slides
+}
+
+/// Place a slide laid out `content_width` × `content_height` on a page
+/// `width` × `height`: scaled down to fit if needed, and centered.
+pub fn fit_slide(
+    document: &str,
+    content_width: f32,
+    content_height: f32,
+    width: f32,
+    height: f32,
+    background: &str,
+) -> PagedDocument {
+    let page = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {0} {1}" width="{0}" height="{1}"><rect width="100%" height="100%" fill="{2}" />"#,
+        width, height, background
+    );
+    // The nested viewport's default `xMidYMid meet` scales and centers.
+    let document = format!(
+        r#"{}<svg x="0" y="0" width="{}" height="{}" viewBox="0 0 {} {:.2}">{}</svg></svg>"#,
+        page,
+        width,
+        height,
+        content_width,
+        content_height,
+        crate::pages::svg_inner(document),
+    );
+    PagedDocument::single_page(width, height, document, background)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_rules_or_headings_and_repeats_front_matter() {
+        let deck = "---\ntitle: Deck\n---\n\n# One\n\nIntro\n\n---\n\n## Two\n\n- a\n\n  ---\n\n  b\n\n---\n\n";
+        let slides = split_slides(deck);
+        assert_eq!(slides.len(), 2);
+        assert!(slides[0].starts_with("---\ntitle: Deck\n---\n\n# One"));
+        assert!(slides[1].contains("## Two") && slides[1].contains("  b"));
+
+        // A setext underline makes an H2, not a separator.
+        let slides = split_slides("# A\n\ntext\n\n## B\n\n### C\n\nSetext\n---\n");
+        assert_eq!(
+            slides,
+            vec!["# A\n\ntext\n\n", "## B\n\n### C\n\n", "Setext\n---\n"]
+        );
+        assert_eq!(split_slides("Just text.\n"), vec!["Just text.\n"]);
+    }
+
+    #[test]
+    fn aspect_ratios() {
+        assert_eq!(SlideAspect::parse("16:9"), Ok(SlideAspect::Wide));
+        assert_eq!(SlideAspect::Standard.height(1200.0), 900.0);
+        assert!(SlideAspect::parse("21:9").is_err());
+    }
+}
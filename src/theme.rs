@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 const GITHUB_LIGHT_BACKGROUND: &str = "#ffffff";
 const GITHUB_LIGHT_TEXT: &str = "#24292f";
@@ -94,6 +95,443 @@ pub struct Theme {
     /// Background fill opacity for table header rows (0.0–1.0).
     #[serde(default = "default_table_header_opacity")]
     pub table_header_opacity: f32,
+    /// Background fill opacity of every other table body row (0.0 turns
+    /// striping off).
+    #[serde(default)]
+    pub table_stripe_opacity: f32,
+    /// Which table borders to draw.
+    #[serde(default)]
+    pub table_borders: TableBorders,
+    /// How tables wider than the page are fitted. A table's own
+    /// `{overflow=...}` attribute overrides this.
+    #[serde(default)]
+    pub table_overflow: TableOverflow,
+
+    /// Bullet glyphs by list nesting depth; deeper lists cycle through them.
+    #[serde(default = "default_list_bullets")]
+    pub list_bullets: Vec<String>,
+    /// Ordered list numbering by nesting depth (`"1"`, `"a"`, `"A"`, `"i"`
+    /// or `"I"`), cycled like `list_bullets`.
+    #[serde(default = "default_list_numbering")]
+    pub list_numbering: Vec<ListNumbering>,
+
+    /// Accent colors of GitHub-style alert blockquotes.
+    #[serde(default)]
+    pub alerts: AlertColors,
+    /// Show `<details>` sections as their summary alone, leaving out the
+    /// body. Front matter `collapse_details` overrides this.
+    #[serde(default)]
+    pub collapse_details: bool,
+
+    /// Horizontal alignment of paragraph text.
+    #[serde(default)]
+    pub text_align: TextAlign,
+    /// Hyphenate words that would otherwise wrap whole, using patterns for
+    /// the front matter `lang` (English by default).
+    #[serde(default)]
+    pub hyphenate: bool,
+
+    /// Prefix block image captions with "Figure N", numbering images in
+    /// document order. Front matter `figure_numbers` overrides this.
+    #[serde(default)]
+    pub figure_numbers: bool,
+
+    /// Prefix headings with section numbers (`1`, `1.1`, `1.1.1`). Front
+    /// matter `number_headings` overrides this.
+    #[serde(default)]
+    pub number_headings: bool,
+    /// Level of the headings numbered `1`, `2`, ...; shallower headings
+    /// stay unnumbered.
+    #[serde(default = "default_heading_number_start")]
+    pub heading_number_start: u8,
+    /// Template of a heading number, with `{n}` replaced by the section
+    /// number (e.g. `"{n}."` or `"§{n}"`).
+    #[serde(default = "default_heading_number_format")]
+    pub heading_number_format: String,
+    /// Size, weight, spacing, case, color, font and bottom rule of each
+    /// heading level.
+    #[serde(default)]
+    pub headings: HeadingStyles,
+
+    /// Running header of paginated output. Slots are templates with
+    /// `{title}`, `{section}`, `{page}`, `{pages}` and `{date}`; front matter
+    /// `page_header` overrides this.
+    #[serde(default = "default_page_header")]
+    pub page_header: PageSlots,
+    /// Running footer of paginated output, like `page_header`.
+    #[serde(default = "default_page_footer")]
+    pub page_footer: PageSlots,
+
+    /// Number of columns the document flows through, each balanced to the
+    /// same height. Front matter `columns` overrides this.
+    #[serde(default = "default_columns")]
+    pub columns: u8,
+    /// Space between columns, in pixels.
+    #[serde(default = "default_column_gutter")]
+    pub column_gutter: f32,
+    /// Let top-level tables, diagrams and display math span all columns
+    /// instead of squeezing into one.
+    #[serde(default = "default_column_span")]
+    pub column_span: bool,
+
+    /// Draw emoji as embedded images of the glyphs PNG and PDF export use,
+    /// so SVG viewers show the same emoji instead of their own.
+    #[serde(default = "default_emoji_images")]
+    pub emoji_images: bool,
+
+    /// Font family preferences and per-script fallback chains.
+    #[serde(default)]
+    pub fonts: FontFallbacks,
+}
+
+/// Border, icon and title colors for `> [!NOTE]`, `> [!TIP]`,
+/// `> [!IMPORTANT]`, `> [!WARNING]` and `> [!CAUTION]` blockquotes. The
+/// background is a light tint of the same color.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertColors {
+    pub note: String,
+    pub tip: String,
+    pub important: String,
+    pub warning: String,
+    pub caution: String,
+}
+
+impl Default for AlertColors {
+    fn default() -> Self {
+        Self {
+            note: "#0969da".to_string(),
+            tip: "#1a7f37".to_string(),
+            important: "#8250df".to_string(),
+            warning: "#9a6700".to_string(),
+            caution: "#d1242f".to_string(),
+        }
+    }
+}
+
+/// Typography of one heading level. `size` and `margin_top` scale the
+/// theme's `font_size_base` and `margin_top`; `color` and `font` fall back
+/// to `heading_color` and the body font. A `border` wider than zero draws
+/// a rule under the heading, as GitHub does for H1 and H2.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeadingStyle {
+    pub size: f32,
+    pub weight: u16,
+    pub margin_top: f32,
+    pub uppercase: bool,
+    pub color: Option<String>,
+    pub font: Option<String>,
+    pub border: f32,
+    pub border_color: Option<String>,
+}
+
+impl HeadingStyle {
+    fn new(size: f32, margin_top: f32) -> Self {
+        Self {
+            size,
+            margin_top,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for HeadingStyle {
+    fn default() -> Self {
+        Self {
+            size: 1.0,
+            weight: 700,
+            margin_top: 1.15,
+            uppercase: false,
+            color: None,
+            font: None,
+            border: 0.0,
+            border_color: None,
+        }
+    }
+}
+
+/// Per-level heading styles, `h1` through `h6`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeadingStyles {
+    pub h1: HeadingStyle,
+    pub h2: HeadingStyle,
+    pub h3: HeadingStyle,
+    pub h4: HeadingStyle,
+    pub h5: HeadingStyle,
+    pub h6: HeadingStyle,
+}
+
+impl HeadingStyles {
+    /// The style of heading `level` (1–6; deeper levels use `h6`).
+    pub fn level(&self, level: u8) -> &HeadingStyle {
+        match level {
+            0 | 1 => &self.h1,
+            2 => &self.h2,
+            3 => &self.h3,
+            4 => &self.h4,
+            5 => &self.h5,
+            _ => &self.h6,
+        }
+    }
+}
+
+impl Default for HeadingStyles {
+    fn default() -> Self {
+        Self {
+            h1: HeadingStyle::new(2.2, 1.6),
+            h2: HeadingStyle::new(1.8, 1.45),
+            h3: HeadingStyle::new(1.5, 1.3),
+            h4: HeadingStyle::new(1.25, 1.15),
+            h5: HeadingStyle::new(1.1, 1.15),
+            h6: HeadingStyle::new(1.0, 1.15),
+        }
+    }
+}
+
+/// Left, center and right slots of a page header or footer. Empty slots
+/// are not drawn.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PageSlots {
+    pub left: String,
+    pub center: String,
+    pub right: String,
+}
+
+impl PageSlots {
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty() && self.center.is_empty() && self.right.is_empty()
+    }
+}
+
+/// Paragraph alignment. `Justify` stretches word gaps so every wrapped line
+/// but the last of a paragraph fills the text width.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+/// Table border styles: every cell, only the outline, or none.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableBorders {
+    #[default]
+    All,
+    Outer,
+    None,
+}
+
+/// Fitting of tables wider than the page. `Wrap` narrows columns and wraps
+/// cell text; `Shrink` scales the table's text down first (to 60% at most);
+/// `Rotate` turns the table sideways when it then fits; `Split` breaks it
+/// into column groups that repeat the first column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableOverflow {
+    #[default]
+    Wrap,
+    Shrink,
+    Rotate,
+    Split,
+}
+
+/// How ordered list items are numbered: `1.`, `a.`, `A.`, `i.` or `I.`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ListNumbering {
+    #[default]
+    #[serde(rename = "1")]
+    Decimal,
+    #[serde(rename = "a")]
+    LowerAlpha,
+    #[serde(rename = "A")]
+    UpperAlpha,
+    #[serde(rename = "i")]
+    LowerRoman,
+    #[serde(rename = "I")]
+    UpperRoman,
+}
+
+impl ListNumbering {
+    /// The label of item `n` (1-based). Letters continue `z`, `aa`, `ab`;
+    /// numbers too large for roman numerals fall back to decimal.
+    pub fn label(self, n: usize) -> String {
+        match self {
+            ListNumbering::Decimal => n.to_string(),
+            ListNumbering::LowerAlpha => alpha_label(n),
+            ListNumbering::UpperAlpha => alpha_label(n).to_ascii_uppercase(),
+            ListNumbering::LowerRoman => roman_label(n),
+            ListNumbering::UpperRoman => roman_label(n).to_ascii_uppercase(),
+        }
+    }
+}
+
+fn alpha_label(mut n: usize) -> String {
+    if n == 0 {
+        return "0".to_string();
+    }
+    let mut letters = Vec::new();
+    while n > 0 {
+        n -= 1;
+        letters.push(b'a' + (n % 26) as u8);
+        n /= 26;
+    }
+    letters.iter().rev().map(|&b| b as char).collect()
+}
+
+fn roman_label(n: usize) -> String {
+    if n == 0 || n >= 4000 {
+        return n.to_string();
+    }
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut rest = n;
+    let mut out = String::new();
+    for (value, numeral) in NUMERALS {
+        while rest >= value {
+            out.push_str(numeral);
+            rest -= value;
+        }
+    }
+    out
+}
+
+/// Font families used for measurement and rasterization.
+///
+/// The generic lists pick the family behind `sans-serif`, `serif` and
+/// `monospace` (first installed entry wins; empty means "pick automatically").
+/// `scripts` maps a script name (`han`, `japanese`, `korean`, `arabic`,
+/// `hebrew`, `devanagari`, `thai`, `cyrillic`, `greek`, `emoji`, `symbols`)
+/// to the families tried, in order, for characters of that script before any
+/// other installed font.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FontFallbacks {
+    #[serde(default)]
+    pub sans_serif: Vec<String>,
+    #[serde(default)]
+    pub serif: Vec<String>,
+    #[serde(default)]
+    pub monospace: Vec<String>,
+    #[serde(default = "default_script_fallbacks")]
+    pub scripts: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for FontFallbacks {
+    fn default() -> Self {
+        Self {
+            sans_serif: Vec::new(),
+            serif: Vec::new(),
+            monospace: Vec::new(),
+            scripts: default_script_fallbacks(),
+        }
+    }
+}
+
+fn default_script_fallbacks() -> BTreeMap<String, Vec<String>> {
+    let chains: &[(&str, &[&str])] = &[
+        (
+            "han",
+            &[
+                "Noto Sans CJK SC",
+                "Noto Sans SC",
+                "Source Han Sans SC",
+                "PingFang SC",
+                "Microsoft YaHei",
+                "WenQuanYi Micro Hei",
+            ],
+        ),
+        (
+            "japanese",
+            &[
+                "Noto Sans CJK JP",
+                "Noto Sans JP",
+                "Source Han Sans JP",
+                "Hiragino Sans",
+                "Yu Gothic",
+                "Meiryo",
+            ],
+        ),
+        (
+            "korean",
+            &[
+                "Noto Sans CJK KR",
+                "Noto Sans KR",
+                "Source Han Sans KR",
+                "Apple SD Gothic Neo",
+                "Malgun Gothic",
+            ],
+        ),
+        (
+            "arabic",
+            &[
+                "Noto Sans Arabic",
+                "Noto Naskh Arabic",
+                "Geeza Pro",
+                "Segoe UI",
+                "DejaVu Sans",
+            ],
+        ),
+        (
+            "hebrew",
+            &[
+                "Noto Sans Hebrew",
+                "Arial Hebrew",
+                "Segoe UI",
+                "DejaVu Sans",
+            ],
+        ),
+        (
+            "devanagari",
+            &["Noto Sans Devanagari", "Kohinoor Devanagari", "Nirmala UI"],
+        ),
+        ("thai", &["Noto Sans Thai", "Thonburi", "Leelawadee UI"]),
+        (
+            "emoji",
+            &[
+                "Noto Color Emoji",
+                "Apple Color Emoji",
+                "Segoe UI Emoji",
+                "Twemoji Mozilla",
+            ],
+        ),
+        (
+            "symbols",
+            &[
+                "Noto Sans Symbols",
+                "Noto Sans Symbols2",
+                "Segoe UI Symbol",
+                "DejaVu Sans",
+            ],
+        ),
+    ];
+    chains
+        .iter()
+        .map(|(script, families)| {
+            (
+                script.to_string(),
+                families.iter().map(|f| f.to_string()).collect(),
+            )
+        })
+        .collect()
 }
 
 fn default_background() -> String {
@@ -147,6 +585,48 @@ fn default_code_radius() -> f32 {
 fn default_table_header_opacity() -> f32 {
     0.08
 }
+fn default_list_bullets() -> Vec<String> {
+    vec!["•".to_string(), "◦".to_string(), "▪".to_string()]
+}
+fn default_list_numbering() -> Vec<ListNumbering> {
+    vec![
+        ListNumbering::Decimal,
+        ListNumbering::LowerAlpha,
+        ListNumbering::LowerRoman,
+    ]
+}
+fn default_heading_number_start() -> u8 {
+    1
+}
+fn default_heading_number_format() -> String {
+    "{n}".to_string()
+}
+fn default_page_header() -> PageSlots {
+    PageSlots {
+        left: "{title}".to_string(),
+        center: String::new(),
+        right: "{section}".to_string(),
+    }
+}
+fn default_page_footer() -> PageSlots {
+    PageSlots {
+        left: "{date}".to_string(),
+        center: String::new(),
+        right: "Page {page} of {pages}".to_string(),
+    }
+}
+fn default_columns() -> u8 {
+    1
+}
+fn default_column_gutter() -> f32 {
+    32.0
+}
+fn default_column_span() -> bool {
+    true
+}
+fn default_emoji_images() -> bool {
+    true
+}
 
 impl Default for Theme {
     fn default() -> Self {
@@ -172,11 +652,23 @@ struct AlacrittyNormal {
     blue: String,
     cyan: String,
     white: String,
+    #[serde(default)]
+    green: Option<String>,
+    #[serde(default)]
+    magenta: Option<String>,
+    #[serde(default)]
+    yellow: Option<String>,
+    #[serde(default)]
+    red: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct AlacrittyTheme {
     colors: AlacrittyColors,
+    /// Optional `[markie]` table overriding any `Theme` field by name, so
+    /// theme files can carry layout and font settings alongside colors.
+    #[serde(default)]
+    markie: Option<serde_json::Value>,
 }
 
 impl Theme {
@@ -208,6 +700,27 @@ impl Theme {
             code_padding_y: CODE_PADDING_Y,
             code_radius: CODE_RADIUS,
             table_header_opacity: 0.08,
+            table_stripe_opacity: 0.0,
+            table_borders: TableBorders::All,
+            table_overflow: TableOverflow::Wrap,
+            list_bullets: default_list_bullets(),
+            list_numbering: default_list_numbering(),
+            alerts: AlertColors::default(),
+            collapse_details: false,
+            text_align: TextAlign::Left,
+            hyphenate: false,
+            figure_numbers: false,
+            number_headings: false,
+            heading_number_start: default_heading_number_start(),
+            heading_number_format: default_heading_number_format(),
+            headings: HeadingStyles::default(),
+            page_header: default_page_header(),
+            page_footer: default_page_footer(),
+            columns: default_columns(),
+            column_gutter: default_column_gutter(),
+            column_span: default_column_span(),
+            emoji_images: true,
+            fonts: FontFallbacks::default(),
         }
     }
 
@@ -247,8 +760,16 @@ impl Theme {
 
     fn from_alacritty_theme(alacritty: AlacrittyTheme) -> Result<Self, String> {
         let colors = alacritty.colors;
-
-        Ok(Theme {
+        let default_alerts = AlertColors::default();
+        let alerts = AlertColors {
+            note: colors.normal.blue.clone(),
+            tip: colors.normal.green.unwrap_or(default_alerts.tip),
+            important: colors.normal.magenta.unwrap_or(default_alerts.important),
+            warning: colors.normal.yellow.unwrap_or(default_alerts.warning),
+            caution: colors.normal.red.unwrap_or(default_alerts.caution),
+        };
+
+        let theme = Theme {
             background_color: colors.primary.background,
             text_color: colors.primary.foreground.clone(),
             heading_color: colors.normal.blue.clone(),
@@ -269,13 +790,64 @@ impl Theme {
             code_padding_y: CODE_PADDING_Y,
             code_radius: CODE_RADIUS,
             table_header_opacity: 0.08,
-        })
+            table_stripe_opacity: 0.0,
+            table_borders: TableBorders::All,
+            table_overflow: TableOverflow::Wrap,
+            list_bullets: default_list_bullets(),
+            list_numbering: default_list_numbering(),
+            alerts,
+            collapse_details: false,
+            text_align: TextAlign::Left,
+            hyphenate: false,
+            figure_numbers: false,
+            number_headings: false,
+            heading_number_start: default_heading_number_start(),
+            heading_number_format: default_heading_number_format(),
+            headings: HeadingStyles::default(),
+            page_header: default_page_header(),
+            page_footer: default_page_footer(),
+            columns: default_columns(),
+            column_gutter: default_column_gutter(),
+            column_span: default_column_span(),
+            emoji_images: true,
+            fonts: FontFallbacks::default(),
+        };
+
+        match alacritty.markie {
+            Some(overrides) => theme.with_overrides(overrides),
+            None => Ok(theme),
+        }
+    }
+
+    /// Apply a table of field overrides (e.g. a theme file's `[markie]`
+    /// section). Nested tables are merged key by key.
+    pub fn with_overrides(self, overrides: serde_json::Value) -> Result<Self, String> {
+        fn merge(base: &mut serde_json::Value, overrides: serde_json::Value) {
+            match (base, overrides) {
+                (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+                    for (key, value) in overrides {
+                        match base.get_mut(&key) {
+                            Some(existing) => merge(existing, value),
+                            None => {
+                                base.insert(key, value);
+                            }
+                        }
+                    }
+                }
+                (base, overrides) => *base = overrides,
+            }
+        }
+
+        let mut value = serde_json::to_value(&self)
+            .map_err(|e| format!("Failed to apply theme overrides: {}", e))?;
+        merge(&mut value, overrides);
+        serde_json::from_value(value).map_err(|e| format!("Invalid [markie] theme settings: {}", e))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Theme;
+    use super::{AlertColors, HeadingStyles, ListNumbering, Theme};
 
     #[test]
     fn from_builtin_accepts_hyphenated_and_case_insensitive_names() {
@@ -286,6 +858,15 @@ mod tests {
         assert_eq!(underscore.text_color, hyphen.text_color);
     }
 
+    #[test]
+    fn list_numbering_labels() {
+        assert_eq!(ListNumbering::Decimal.label(12), "12");
+        assert_eq!(ListNumbering::LowerAlpha.label(1), "a");
+        assert_eq!(ListNumbering::UpperAlpha.label(28), "AB");
+        assert_eq!(ListNumbering::LowerRoman.label(14), "xiv");
+        assert_eq!(ListNumbering::UpperRoman.label(1994), "MCMXCIV");
+    }
+
     #[test]
     fn fallback_uses_github_light_for_unknown_builtin_theme() {
         let fallback = Theme::from_builtin_or_fallback("does_not_exist");
@@ -295,4 +876,110 @@ mod tests {
         assert_eq!(fallback.text_color, github_light.text_color);
         assert_eq!(fallback.code_bg_color, github_light.code_bg_color);
     }
+
+    #[test]
+    fn markie_section_overrides_fonts_and_keeps_colors() {
+        let content = r##"
+[colors.primary]
+background = '#fdf6e3'
+foreground = '#586e75'
+
+[colors.normal]
+black = '#073642'
+blue = '#268bd2'
+cyan = '#2aa198'
+white = '#eee8d5'
+
+[markie]
+font_size_base = 18.0
+
+[markie.fonts]
+sans_serif = ["Inter"]
+
+[markie.fonts.scripts]
+han = ["LXGW WenKai"]
+"##;
+        let theme = Theme::from_alacritty_toml(content).expect("theme with overrides");
+
+        assert_eq!(theme.background_color, "#fdf6e3");
+        assert_eq!(theme.font_size_base, 18.0);
+        assert_eq!(theme.fonts.sans_serif, vec!["Inter".to_string()]);
+        assert_eq!(theme.fonts.scripts["han"], vec!["LXGW WenKai".to_string()]);
+        // Scripts not mentioned keep their default chains.
+        assert!(theme.fonts.scripts["emoji"].contains(&"Noto Color Emoji".to_string()));
+    }
+
+    #[test]
+    fn alert_colors_follow_palette_and_markie_overrides() {
+        let content = r##"
+[colors.primary]
+background = '#fdf6e3'
+foreground = '#586e75'
+
+[colors.normal]
+black = '#073642'
+blue = '#268bd2'
+cyan = '#2aa198'
+white = '#eee8d5'
+green = '#859900'
+
+[markie.alerts]
+caution = '#ff0000'
+"##;
+        let theme = Theme::from_alacritty_toml(content).expect("theme with alert colors");
+
+        assert_eq!(theme.alerts.note, "#268bd2");
+        assert_eq!(theme.alerts.tip, "#859900");
+        assert_eq!(theme.alerts.caution, "#ff0000");
+        // Colors missing from the palette fall back to GitHub's.
+        assert_eq!(theme.alerts.warning, AlertColors::default().warning);
+    }
+
+    #[test]
+    fn markie_heading_overrides_keep_other_levels_and_fields() {
+        let content = r##"
+[colors.primary]
+background = '#fdf6e3'
+foreground = '#586e75'
+
+[colors.normal]
+black = '#073642'
+blue = '#268bd2'
+cyan = '#2aa198'
+white = '#eee8d5'
+
+[markie.headings.h1]
+uppercase = true
+border = 1.0
+font = "Georgia"
+"##;
+        let theme = Theme::from_alacritty_toml(content).expect("theme with heading styles");
+        let defaults = HeadingStyles::default();
+
+        assert!(theme.headings.h1.uppercase);
+        assert_eq!(theme.headings.h1.border, 1.0);
+        assert_eq!(theme.headings.h1.font.as_deref(), Some("Georgia"));
+        assert_eq!(theme.headings.h1.size, defaults.h1.size);
+        assert_eq!(theme.headings.level(2), &defaults.h2);
+    }
+
+    #[test]
+    fn invalid_markie_section_is_an_error() {
+        let content = r##"
+[colors.primary]
+background = '#fdf6e3'
+foreground = '#586e75'
+
+[colors.normal]
+black = '#073642'
+blue = '#268bd2'
+cyan = '#2aa198'
+white = '#eee8d5'
+
+[markie]
+font_size_base = "large"
+"##;
+        let err = Theme::from_alacritty_toml(content).unwrap_err();
+        assert!(err.contains("Invalid [markie] theme settings"), "{err}");
+    }
 }